@@ -0,0 +1,3509 @@
+use chrono::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+const DREAMS_FILE: &str = "dreams.json";
+const CONFIG_FILE: &str = "config.json";
+const STATS_FILE: &str = "stats.json";
+const PROMPTS_FILE: &str = "prompts.txt";
+const DAILY_LOG_FILE: &str = "daily_logs.json";
+const TECHNIQUES_FILE: &str = "techniques.json";
+const ALARMS_FILE: &str = "alarms.json";
+const TECHNIQUE_HISTORY_FILE: &str = "technique_history.jsonl";
+const REALITY_CHECK_LOG_FILE: &str = "reality_check_log.jsonl";
+const ALARM_LOG_FILE: &str = "alarm_log.jsonl";
+const LEGACY_TECHNIQUE_HISTORY_FILE: &str = "technique_history.json";
+const UNDO_FILE: &str = ".undo.json";
+const UNDO_SUFFIX: &str = ".undo";
+const REPORT_MARKERS_FILE: &str = ".report_markers.json";
+const MANIFEST_FILE: &str = "manifest.json";
+const LAST_ALARM_ID_FILE: &str = ".last_alarm_id.json";
+const DREAMS_DIR: &str = "dreams";
+
+/// Version of the on-disk data layout. Journals without a manifest are version 1.
+pub const SCHEMA_VERSION: u32 = 5;
+
+pub const TREND_DAYS: usize = 30;
+/// Most frequent words kept in `stats.json`, so the file doesn't grow with every new word.
+pub const COMMON_WORDS_KEPT: usize = 200;
+pub const VIVIDNESS_SCALE: u8 = 5;
+/// Settings offered when adding a dream; anything else can be typed in instead.
+pub const DREAM_SETTINGS: &[&str] = &["home", "work", "school", "nature", "city", "travel", "fantasy", "abstract"];
+/// Rated dreams with a sleep log needed before vividness is correlated with sleep quality.
+pub const MIN_CORRELATION_DREAMS: usize = 5;
+/// Nights needed before `stats` shows sleep percentiles; with fewer they say little beyond the average.
+pub const MIN_PERCENTILE_NIGHTS: usize = 5;
+/// On-disk layout of every calendar date in the journal.
+pub const DATE_FORMAT: &str = "%Y-%m-%d";
+const SPARK_BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Serde adapter storing a `NaiveDate` as `DATE_FORMAT`, with an error naming the bad value.
+mod ymd {
+    use super::DATE_FORMAT;
+    use chrono::NaiveDate;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(date: &NaiveDate, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&date.format(DATE_FORMAT))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        NaiveDate::parse_from_str(&text, DATE_FORMAT)
+            .map_err(|_| serde::de::Error::custom(format!("invalid date '{}', expected YYYY-MM-DD", text)))
+    }
+
+    pub mod option {
+        use super::DATE_FORMAT;
+        use chrono::NaiveDate;
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(date: &Option<NaiveDate>, serializer: S) -> Result<S::Ok, S::Error> {
+            match date {
+                Some(date) => serializer.collect_str(&date.format(DATE_FORMAT)),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NaiveDate>, D::Error> {
+            Option::<String>::deserialize(deserializer)?
+                .map(|text| NaiveDate::parse_from_str(&text, DATE_FORMAT)
+                    .map_err(|_| serde::de::Error::custom(format!("invalid date '{}', expected YYYY-MM-DD", text))))
+                .transpose()
+        }
+    }
+}
+
+/// Writes a map with its keys in order, so saved files and JSON reports don't reshuffle between runs.
+fn sorted_map<K: Ord + Serialize, V: Serialize, S: serde::Serializer>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_map(map.iter().collect::<std::collections::BTreeMap<_, _>>())
+}
+
+/// Reads `sleep` as a list of sessions, or as the single session (or `null`) kept before version 5.
+fn sleep_sessions<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<SleepLog>, D::Error> {
+    use serde::de::Error;
+
+    let value = serde_json::Value::deserialize(deserializer)?;
+    match value {
+        serde_json::Value::Null => Ok(Vec::new()),
+        serde_json::Value::Array(_) => serde_json::from_value(value).map_err(D::Error::custom),
+        single => Ok(vec![serde_json::from_value(single).map_err(D::Error::custom)?]),
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SleepLog {
+    #[serde(with = "ymd")]
+    pub date: NaiveDate,
+    pub bedtime: String,
+    pub wake_time: String,
+    pub quality: u8,
+    pub notes: String,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct DailyLog {
+    #[serde(with = "ymd")]
+    pub date: NaiveDate,
+    pub dream: Option<Dream>,
+    /// Every sleep of the day: the night first, then any naps.
+    #[serde(default, deserialize_with = "sleep_sessions")]
+    pub sleep: Vec<SleepLog>,
+    pub wake_feeling: Option<String>,
+    pub reality_checks: u32,
+    pub notes: String,
+    pub technique_practice: Option<TechniquePractice>,
+    pub wbtb_alarm_used: Option<u32>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Minutes you actually stayed awake during the WBTB break, which can differ from the alarm's plan.
+    #[serde(default)]
+    pub wbtb_awake_minutes: Option<u32>,
+}
+
+impl DailyLog {
+    /// The longest sleep of the day, normally the night rather than a nap.
+    pub fn main_sleep(&self) -> Option<&SleepLog> {
+        self.sleep.iter().rev().max_by_key(|sleep| sleep_minutes(sleep).unwrap_or(0))
+    }
+
+    /// Minutes slept across all of the day's sessions.
+    pub fn total_sleep_minutes(&self) -> Option<i64> {
+        let minutes: Vec<i64> = self.sleep.iter().filter_map(sleep_minutes).collect();
+        (!minutes.is_empty()).then(|| minutes.iter().sum())
+    }
+
+    /// Mean quality rating of the day's sessions.
+    pub fn sleep_quality(&self) -> Option<f32> {
+        (!self.sleep.is_empty()).then(|| {
+            self.sleep.iter().map(|sleep| f32::from(sleep.quality)).sum::<f32>() / self.sleep.len() as f32
+        })
+    }
+
+    /// Minutes awake during the day's WBTB break: as logged, or else the used alarm's planned period.
+    pub fn awake_minutes(&self, alarms: &[WBTBAlarm]) -> Option<u32> {
+        self.wbtb_awake_minutes.or_else(|| {
+            let id = self.wbtb_alarm_used?;
+            alarms.iter().find(|a| a.id == id).map(|a| a.awake_minutes)
+        })
+    }
+
+    /// Folds another log for the same date into this one. Empty fields are filled from `other`,
+    /// sleeps, notes and tags are combined without repeats, and the higher reality-check count
+    /// is kept, since a second entry for a day normally re-records that day's total.
+    pub fn merge(&mut self, other: DailyLog) {
+        self.dream = self.dream.take().or(other.dream);
+        for sleep in other.sleep {
+            if !self.sleep.iter().any(|s| s.bedtime == sleep.bedtime && s.wake_time == sleep.wake_time) {
+                self.sleep.push(sleep);
+            }
+        }
+        if self.wake_feeling.as_deref().is_none_or(|f| f.trim().is_empty()) {
+            self.wake_feeling = other.wake_feeling;
+        }
+        self.reality_checks = self.reality_checks.max(other.reality_checks);
+        let notes = other.notes.trim();
+        if self.notes.trim().is_empty() {
+            self.notes = notes.to_string();
+        } else if !notes.is_empty() && !self.notes.contains(notes) {
+            self.notes = format!("{}\n{}", self.notes.trim_end(), notes);
+        }
+        self.technique_practice = self.technique_practice.take().or(other.technique_practice);
+        self.wbtb_alarm_used = self.wbtb_alarm_used.or(other.wbtb_alarm_used);
+        self.wbtb_awake_minutes = self.wbtb_awake_minutes.or(other.wbtb_awake_minutes);
+        for tag in other.tags {
+            if !self.tags.contains(&tag) {
+                self.tags.push(tag);
+            }
+        }
+    }
+}
+
+/// Merges logs sharing a date into the first log for that date, keeping the order of first
+/// appearance. Returns the merged dates with how many logs each had.
+pub fn merge_duplicate_logs(logs: Vec<DailyLog>) -> (Vec<DailyLog>, Vec<(NaiveDate, usize)>) {
+    let mut merged: Vec<DailyLog> = Vec::new();
+    let mut counts: HashMap<NaiveDate, usize> = HashMap::new();
+    for log in logs {
+        *counts.entry(log.date).or_insert(0) += 1;
+        match merged.iter_mut().find(|l| l.date == log.date) {
+            Some(existing) => existing.merge(log),
+            None => merged.push(log),
+        }
+    }
+
+    let mut duplicates: Vec<(NaiveDate, usize)> = counts.into_iter().filter(|(_, count)| *count > 1).collect();
+    duplicates.sort();
+    (merged, duplicates)
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Dream {
+    pub id: u32,
+    #[serde(with = "ymd")]
+    pub date: NaiveDate,
+    pub title: String,
+    pub content: String,
+    pub tags: Vec<String>,
+    pub lucid: Option<bool>,
+    pub dream_sign: Option<String>,
+    #[serde(default)]
+    pub links: Vec<u32>,
+    /// How vivid and complete the recall was, 1 to `VIVIDNESS_SCALE`.
+    #[serde(default)]
+    pub vividness: Option<u8>,
+    /// Marked as personally significant, to find again with `dream list --starred`.
+    #[serde(default)]
+    pub starred: bool,
+    /// Where the dream mostly took place, usually one of `DREAM_SETTINGS`.
+    #[serde(default)]
+    pub setting: Option<String>,
+}
+
+pub const DEFAULT_PROMPT_CATEGORY: &str = "default";
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum RealityCheckPrompt {
+    Plain(String),
+    Detailed {
+        text: String,
+        #[serde(default)]
+        category: Option<String>,
+        #[serde(default = "default_prompt_weight")]
+        weight: f32,
+    },
+}
+
+fn default_prompt_weight() -> f32 {
+    1.0
+}
+
+impl RealityCheckPrompt {
+    pub fn text(&self) -> &str {
+        match self {
+            RealityCheckPrompt::Plain(text) | RealityCheckPrompt::Detailed { text, .. } => text,
+        }
+    }
+
+    pub fn category(&self) -> &str {
+        match self {
+            RealityCheckPrompt::Detailed { category: Some(category), .. } => category,
+            _ => DEFAULT_PROMPT_CATEGORY,
+        }
+    }
+
+    pub fn weight(&self) -> f32 {
+        match self {
+            RealityCheckPrompt::Plain(_) => 1.0,
+            RealityCheckPrompt::Detailed { weight, .. } => *weight,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+pub enum TimeFormat {
+    #[default]
+    #[serde(rename = "24h")]
+    H24,
+    #[serde(rename = "12h")]
+    H12,
+}
+
+impl TimeFormat {
+    pub fn time_pattern(&self) -> &'static str {
+        match self {
+            TimeFormat::H24 => "%H:%M",
+            TimeFormat::H12 => "%-I:%M %p",
+        }
+    }
+
+    pub fn format(&self, time: NaiveTime) -> String {
+        time.format(self.time_pattern()).to_string()
+    }
+
+    /// Formats a stored HH:MM string, leaving it untouched if it doesn't parse.
+    pub fn format_str(&self, time: &str) -> String {
+        NaiveTime::parse_from_str(time, "%H:%M")
+            .map(|t| self.format(t))
+            .unwrap_or_else(|_| time.to_string())
+    }
+
+    pub fn format_datetime(&self, datetime: &DateTime<Local>) -> String {
+        format!("{} {}", datetime.format("%Y-%m-%d"), self.format(datetime.time()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorTheme {
+    #[default]
+    Default,
+    Muted,
+    Mono,
+}
+
+/// A WBTB alarm shape: how long after bedtime to wake and how long to stay up.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct AlarmPreset {
+    pub sleep_minutes: u32,
+    pub awake_minutes: u32,
+}
+
+/// Presets available without any config, built from ~90-minute sleep cycles.
+pub fn builtin_alarm_presets() -> Vec<(&'static str, AlarmPreset)> {
+    vec![
+        ("wbtb-45", AlarmPreset { sleep_minutes: 270, awake_minutes: 30 }),
+        ("wbtb-6h", AlarmPreset { sleep_minutes: 360, awake_minutes: 20 }),
+        ("nap", AlarmPreset { sleep_minutes: 90, awake_minutes: 0 }),
+    ]
+}
+
+/// Where dreams live: one `dreams.json` array, or a `dreams/<id>-<slug>.md` file per dream.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum DreamStorage {
+    #[default]
+    Json,
+    Files,
+}
+
+/// Day calendar weeks start on in weekly reports and week ranges.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum WeekStart {
+    #[default]
+    #[serde(alias = "mon")]
+    Monday,
+    #[serde(alias = "sun")]
+    Sunday,
+}
+
+impl WeekStart {
+    pub fn weekday(self) -> Weekday {
+        match self {
+            WeekStart::Monday => Weekday::Mon,
+            WeekStart::Sunday => Weekday::Sun,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub reality_check_prompts: Vec<RealityCheckPrompt>,
+    pub wbtb_activities: Vec<String>,
+    pub dry_spell_days: u32,
+    pub time_format: TimeFormat,
+    pub color_theme: ColorTheme,
+    pub week_start: WeekStart,
+    /// Local hour after which a missing daily log triggers a reminder; `null` turns it off.
+    pub daily_reminder_hour: Option<u32>,
+    /// Show the daily log reminder after every command, not just `status`.
+    pub daily_reminder_everywhere: bool,
+    pub dream_storage: DreamStorage,
+    /// Also announce alarms and reality checks with desktop notifications.
+    pub desktop_notifications: bool,
+    /// Highest sleep quality rating; ratings run from 1 to this.
+    pub quality_scale: u8,
+    /// Nightly sleep goal, used for the sleep-debt figure in `stats`.
+    pub sleep_target_hours: f32,
+    /// Leave the temporary files written by `dream open` instead of removing them.
+    pub keep_opened_files: bool,
+    /// Extra `alarm set --preset` names; these override built-ins of the same name.
+    pub alarm_presets: HashMap<String, AlarmPreset>,
+    /// Days without practice after which `train status` marks a technique as due.
+    pub technique_due_days: u32,
+    /// Keyword patterns mapped to tags suggested for new dreams, e.g. `"water|ocean|sea": "#water"`.
+    /// Alternatives are separated by `|`, and a trailing `*` matches any word starting with it.
+    pub tag_rules: HashMap<String, String>,
+    /// Pitch and length of the WBTB alarm tone, when built with the `sound` feature.
+    pub alarm_tone_hz: f32,
+    pub alarm_tone_ms: u64,
+    /// Hours of sleep before a WBTB alarm that techniques work best after; `alarm set` warns outside them.
+    pub wbtb_min_sleep_hours: f32,
+    pub wbtb_max_sleep_hours: f32,
+    /// Everyday triggers, such as doorways or your phone, that `reality-check --anchor` ties checks to.
+    pub reality_check_anchors: Vec<String>,
+    /// Section headings new dreams are written under, e.g. `["Setting", "Characters"]`; empty is freeform.
+    pub dream_template: Vec<String>,
+    /// Outcomes `train` offers after the built-in three, e.g. a false awakening counted as a success.
+    pub custom_outcomes: Vec<CustomOutcome>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            reality_check_prompts: Vec::new(),
+            wbtb_activities: Vec::new(),
+            dry_spell_days: 3,
+            time_format: TimeFormat::default(),
+            color_theme: ColorTheme::default(),
+            week_start: WeekStart::default(),
+            daily_reminder_hour: Some(10),
+            daily_reminder_everywhere: false,
+            dream_storage: DreamStorage::default(),
+            desktop_notifications: false,
+            quality_scale: 5,
+            sleep_target_hours: 8.0,
+            keep_opened_files: false,
+            alarm_presets: HashMap::new(),
+            technique_due_days: 7,
+            tag_rules: HashMap::new(),
+            alarm_tone_hz: 880.0,
+            alarm_tone_ms: 1000,
+            wbtb_min_sleep_hours: 4.0,
+            wbtb_max_sleep_hours: 6.0,
+            reality_check_anchors: Vec::new(),
+            dream_template: Vec::new(),
+            custom_outcomes: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn alarm_preset(&self, name: &str) -> Option<AlarmPreset> {
+        self.alarm_presets.get(name).copied().or_else(|| {
+            builtin_alarm_presets().into_iter()
+                .find(|(preset, _)| *preset == name)
+                .map(|(_, preset)| preset)
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct Statistics {
+    pub total_dreams: u32,
+    pub lucid_dreams: u32,
+    #[serde(serialize_with = "sorted_map")]
+    pub common_words: HashMap<String, u32>,
+    #[serde(serialize_with = "sorted_map")]
+    pub dream_calendar: HashMap<String, u32>,
+    #[serde(serialize_with = "sorted_map")]
+    pub technique_effectiveness: HashMap<String, TechniqueStats>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct TechniqueStats {
+    pub attempts: u32,
+    pub successes: u32,
+    #[serde(with = "ymd")]
+    pub last_practiced: NaiveDate,
+    pub success_rate: f32,
+    #[serde(serialize_with = "sorted_map")]
+    pub optimal_conditions: HashMap<String, f32>,
+    /// Success rate with each session weighted by how recent it is (see `RECENCY_HALF_LIFE_DAYS`).
+    #[serde(default)]
+    pub weighted_success_rate: f32,
+    /// Summed `duration_minutes` of every session.
+    #[serde(default)]
+    pub total_minutes: u32,
+    #[serde(default)]
+    pub average_minutes: f32,
+}
+
+impl TechniqueStats {
+    /// `optimal_conditions` from most to least common among successes, ties broken by name.
+    pub fn ranked_conditions(&self) -> Vec<(&String, f32)> {
+        let mut ranked: Vec<_> = self.optimal_conditions.iter().map(|(condition, rate)| (condition, *rate)).collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        ranked
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TechniqueData {
+    pub name: String,
+    pub description: String,
+    pub steps: Vec<String>,
+    #[serde(with = "ymd::option")]
+    pub last_practiced: Option<NaiveDate>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TechniquePractice {
+    pub technique: String,
+    #[serde(with = "ymd")]
+    pub date: NaiveDate,
+    pub duration_minutes: u32,
+    pub outcome: TechniqueOutcome,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(tag = "type", content = "data")]
+pub enum TechniqueOutcome {
+    Unattempted,
+    Failed,
+    PartialLucid,
+    FullLucid { control_level: u8 },
+    /// One of the config's `custom_outcomes`, with whether it counted as a success when recorded.
+    Custom { label: String, success: bool },
+}
+
+impl TechniqueOutcome {
+    pub fn label(&self) -> &str {
+        match self {
+            TechniqueOutcome::Unattempted => "Unattempted",
+            TechniqueOutcome::Failed => "Failed",
+            TechniqueOutcome::PartialLucid => "PartialLucid",
+            TechniqueOutcome::FullLucid { .. } => "FullLucid",
+            TechniqueOutcome::Custom { label, .. } => label,
+        }
+    }
+
+    /// Whether the session counts towards a technique's success rate.
+    pub fn is_success(&self) -> bool {
+        match self {
+            TechniqueOutcome::PartialLucid | TechniqueOutcome::FullLucid { .. } => true,
+            TechniqueOutcome::Custom { success, .. } => *success,
+            TechniqueOutcome::Unattempted | TechniqueOutcome::Failed => false,
+        }
+    }
+}
+
+/// An extra practice outcome offered by `train`, such as a false awakening.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct CustomOutcome {
+    pub label: String,
+    /// Count sessions with this outcome as successes in the effectiveness report.
+    pub success: bool,
+    /// Technique keys to offer it for; empty offers it for every technique.
+    #[serde(default)]
+    pub techniques: Vec<String>,
+}
+
+impl CustomOutcome {
+    pub fn applies_to(&self, technique: &str) -> bool {
+        self.techniques.is_empty() || self.techniques.iter().any(|t| t.eq_ignore_ascii_case(technique))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct WBTBAlarm {
+    pub id: u32,
+    pub bedtime: String,
+    pub wake_time: String,
+    pub awake_minutes: u32,
+    pub enabled: bool,
+    #[serde(with = "ymd::option")]
+    pub last_triggered: Option<NaiveDate>,
+    pub success: Option<bool>,
+    /// Whether `success` came from a lucid dream that night rather than from your answer.
+    #[serde(default)]
+    pub success_inferred: bool,
+    /// Wake with the screen flash only, no sound.
+    #[serde(default)]
+    pub silent: bool,
+}
+
+impl WBTBAlarm {
+    /// Whether this alarm already has the given schedule; times match by value, so `7:05` equals `07:05`.
+    pub fn same_schedule(&self, bedtime: &str, wake_time: &str, awake_minutes: u32) -> bool {
+        let same_time = |a: &str, b: &str| match (NaiveTime::parse_from_str(a.trim(), "%H:%M"), NaiveTime::parse_from_str(b.trim(), "%H:%M")) {
+            (Ok(a), Ok(b)) => a == b,
+            _ => a.trim() == b.trim(),
+        };
+        self.awake_minutes == awake_minutes && same_time(&self.bedtime, bedtime) && same_time(&self.wake_time, wake_time)
+    }
+}
+
+/// One reality check and whether it brought any lucidity or heightened awareness.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RealityCheckRecord {
+    pub timestamp: String,
+    pub prompt: String,
+    pub aware: bool,
+    /// The everyday trigger that prompted the check, if one was named.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum AlarmEventKind {
+    Scheduled,
+    Triggered,
+    AwakeStarted,
+    Completed,
+    Canceled,
+}
+
+impl AlarmEventKind {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AlarmEventKind::Scheduled => "scheduled",
+            AlarmEventKind::Triggered => "triggered",
+            AlarmEventKind::AwakeStarted => "awake-started",
+            AlarmEventKind::Completed => "completed",
+            AlarmEventKind::Canceled => "canceled",
+        }
+    }
+}
+
+/// Something that happened to a scheduled alarm, stamped in local time.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AlarmEvent {
+    pub timestamp: String,
+    pub alarm_id: u32,
+    pub kind: AlarmEventKind,
+    #[serde(default)]
+    pub detail: String,
+}
+
+/// Reality-check habit against lucid dreaming for one week.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RealityCheckWeek {
+    /// First day of the week, on the configured `week_start`.
+    pub start: NaiveDate,
+    /// Checks counted in daily logs.
+    pub checks: u32,
+    /// Logged checks that triggered awareness.
+    pub aware: u32,
+    pub dreams: u32,
+    pub lucid: u32,
+}
+
+/// How often one reality-check anchor has prompted a logged check.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AnchorUsage {
+    pub anchor: String,
+    pub checks: u32,
+    /// Checks from this anchor that triggered awareness.
+    pub aware: u32,
+    pub last_used: Option<NaiveDate>,
+}
+
+impl AnchorUsage {
+    /// Not used in the last `days` days, counting a never-used anchor as neglected.
+    pub fn neglected(&self, today: NaiveDate, days: i64) -> bool {
+        self.last_used.is_none_or(|date| (today - date).num_days() >= days)
+    }
+}
+
+/// Records which file the last destructive operation snapshotted, so it can be restored.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct UndoRecord {
+    pub file: String,
+    pub operation: String,
+    pub created: String,
+    /// Other files the operation changed, restored along with `file`.
+    #[serde(default)]
+    pub also: Vec<String>,
+}
+
+/// When a report last ran with `--since-last`, and the newest dream it covered.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct ReportMarker {
+    pub at: DateTime<Utc>,
+    pub last_dream_id: u32,
+}
+
+impl ReportMarker {
+    /// Whether `dream` was added after this report ran. Dream ids only grow, so this holds even for
+    /// dreams backdated to before the run.
+    pub fn is_new_dream(&self, dream: &Dream) -> bool {
+        dream.id > self.last_dream_id
+    }
+
+    /// Whether `log` is for a day after this report ran, or records a dream added since.
+    pub fn is_new_log(&self, log: &DailyLog) -> bool {
+        log.date > self.at.date_naive() || log.dream.as_ref().is_some_and(|dream| self.is_new_dream(dream))
+    }
+}
+
+/// Problems found in one data file by [`Journal::verify`].
+#[derive(Debug, Clone)]
+pub struct FileReport {
+    pub file: String,
+    pub problems: Vec<String>,
+}
+
+impl FileReport {
+    fn new(file: &str) -> FileReport {
+        FileReport { file: file.to_string(), problems: Vec::new() }
+    }
+
+    pub fn passed(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Central record of the schema version the journal's files were last written with.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+struct Manifest {
+    schema_version: u32,
+}
+
+/// A dream search query: OR-separated groups of terms that must all match (AND).
+#[derive(Debug, Clone, PartialEq)]
+pub struct SearchQuery {
+    groups: Vec<Vec<String>>,
+}
+
+impl SearchQuery {
+    /// Parses `flying AND ocean`, `cat OR dog` and `"old house" ocean` (bare spaces mean AND).
+    pub fn parse(query: &str) -> SearchQuery {
+        let mut tokens: Vec<(String, bool)> = Vec::new();
+        let mut current = String::new();
+        let mut quoted = false;
+        let mut was_quoted = false;
+
+        for c in query.chars() {
+            match c {
+                '"' => {
+                    quoted = !quoted;
+                    was_quoted = true;
+                }
+                c if c.is_whitespace() && !quoted => {
+                    if !current.is_empty() {
+                        tokens.push((std::mem::take(&mut current), was_quoted));
+                    }
+                    was_quoted = false;
+                }
+                c => current.push(c),
+            }
+        }
+        if !current.is_empty() {
+            tokens.push((current, was_quoted));
+        }
+
+        let mut groups = vec![Vec::new()];
+        for (token, quoted) in tokens {
+            match token.as_str() {
+                "OR" if !quoted => groups.push(Vec::new()),
+                "AND" if !quoted => {}
+                _ => groups.last_mut().unwrap().push(token.to_lowercase()),
+            }
+        }
+        groups.retain(|group| !group.is_empty());
+
+        SearchQuery { groups }
+    }
+
+    pub fn term_count(&self) -> usize {
+        self.groups.iter().map(|group| group.len()).sum()
+    }
+
+    /// Every distinct term of the query, in lowercase.
+    pub fn terms(&self) -> Vec<&str> {
+        let mut terms: Vec<&str> = self.groups.iter().flatten().map(String::as_str).collect();
+        terms.sort();
+        terms.dedup();
+        terms
+    }
+
+    pub fn matches(&self, dream: &Dream) -> bool {
+        let title = dream.title.to_lowercase();
+        let content = dream.content.to_lowercase();
+        let tags: Vec<String> = dream.tags.iter().map(|t| t.to_lowercase()).collect();
+
+        self.groups.iter().any(|group| {
+            group.iter().all(|term| {
+                title.contains(term) || content.contains(term) || tags.iter().any(|t| t.contains(term))
+            })
+        })
+    }
+
+    pub fn describe(&self) -> String {
+        let groups: Vec<String> = self.groups.iter()
+            .map(|group| {
+                let terms: Vec<String> = group.iter().map(|t| format!("\"{}\"", t)).collect();
+                if group.len() > 1 && self.groups.len() > 1 {
+                    format!("({})", terms.join(" AND "))
+                } else {
+                    terms.join(" AND ")
+                }
+            })
+            .collect();
+        groups.join(" OR ")
+    }
+}
+
+/// Failures a caller may want to tell apart, e.g. to pick an exit code. They travel inside
+/// `anyhow::Error`, so look for them with `downcast_ref`.
+#[derive(Debug)]
+pub enum JournalError {
+    /// A dream, alarm or technique that doesn't exist.
+    NotFound(String),
+    /// An argument, answer or setting that can't be used.
+    InvalidInput(String),
+    /// A data file that can't be read back.
+    Corrupt(String),
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JournalError::NotFound(message)
+            | JournalError::InvalidInput(message)
+            | JournalError::Corrupt(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for JournalError {}
+
+/// A dream journal stored as JSON files inside a single directory.
+#[derive(Clone, Debug)]
+pub struct Journal {
+    dir: PathBuf,
+    verbose: bool,
+}
+
+impl Journal {
+    pub fn open(dir: impl AsRef<Path>) -> anyhow::Result<Journal> {
+        Journal::open_verbose(dir, false)
+    }
+
+    /// Like [`Journal::open`], but with `verbose` set every file read and write, including
+    /// those of the upgrade, is logged to stderr with its absolute path.
+    pub fn open_verbose(dir: impl AsRef<Path>, verbose: bool) -> anyhow::Result<Journal> {
+        let journal = Journal { dir: dir.as_ref().to_path_buf(), verbose };
+        journal.create_dir(&journal.dir)?;
+        journal.migrate()?;
+        Ok(journal)
+    }
+
+    fn trace(&self, action: &str, paths: &[&Path]) {
+        if self.verbose {
+            let paths: Vec<String> = paths.iter()
+                .map(|path| std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()).display().to_string())
+                .collect();
+            eprintln!("[{}] {}", action, paths.join(" -> "));
+        }
+    }
+
+    fn read(&self, path: impl AsRef<Path>) -> std::io::Result<String> {
+        self.trace("read", &[path.as_ref()]);
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: impl AsRef<Path>, contents: impl AsRef<[u8]>) -> std::io::Result<()> {
+        self.trace("write", &[path.as_ref()]);
+        fs::write(path, contents)
+    }
+
+    fn rename(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> std::io::Result<()> {
+        self.trace("rename", &[from.as_ref(), to.as_ref()]);
+        fs::rename(from, to)
+    }
+
+    fn copy(&self, from: impl AsRef<Path>, to: impl AsRef<Path>) -> std::io::Result<u64> {
+        self.trace("copy", &[from.as_ref(), to.as_ref()]);
+        fs::copy(from, to)
+    }
+
+    fn remove(&self, path: impl AsRef<Path>) -> std::io::Result<()> {
+        self.trace("remove", &[path.as_ref()]);
+        fs::remove_file(path)
+    }
+
+    fn list(&self, dir: impl AsRef<Path>) -> std::io::Result<fs::ReadDir> {
+        self.trace("list", &[dir.as_ref()]);
+        fs::read_dir(dir)
+    }
+
+    fn create_dir(&self, dir: impl AsRef<Path>) -> std::io::Result<()> {
+        if !dir.as_ref().is_dir() {
+            self.trace("create", &[dir.as_ref()]);
+        }
+        fs::create_dir_all(dir)
+    }
+
+    fn remove_dir(&self, dir: impl AsRef<Path>) -> std::io::Result<()> {
+        self.trace("remove", &[dir.as_ref()]);
+        fs::remove_dir_all(dir)
+    }
+
+    /// Upgrades data files written by older versions to the current schema, one version at a time.
+    fn migrate(&self) -> anyhow::Result<()> {
+        let manifest: Manifest = self.load_json(MANIFEST_FILE)?;
+        let mut version = manifest.schema_version.max(1);
+        if version > SCHEMA_VERSION {
+            anyhow::bail!("Journal uses schema version {}, but this build only understands up to {}",
+                version, SCHEMA_VERSION);
+        }
+
+        // Version 4 stores dates as typed values, which older files may not parse as. They are
+        // repaired before any other step loads them through the current types.
+        if version < 4 {
+            self.repair_dates()?;
+        }
+
+        while version < SCHEMA_VERSION {
+            match version {
+                1 => self.migrate_v1_to_v2()?,
+                2 => self.migrate_v2_to_v3()?,
+                3 => {}
+                4 => self.migrate_v4_to_v5()?,
+                _ => unreachable!(),
+            }
+            version += 1;
+        }
+
+        if manifest.schema_version != SCHEMA_VERSION {
+            self.save_json(MANIFEST_FILE, &Manifest { schema_version: SCHEMA_VERSION })?;
+        }
+        Ok(())
+    }
+
+    /// Version 2 added dream links and day tags; rewriting through the current types fills their defaults.
+    fn migrate_v1_to_v2(&self) -> anyhow::Result<()> {
+        if self.path(DREAMS_FILE).exists() {
+            self.save_dreams(&self.dreams()?)?;
+        }
+        if self.path(DAILY_LOG_FILE).exists() {
+            self.save_daily_logs(&self.daily_logs()?)?;
+        }
+        Ok(())
+    }
+
+    /// Version 3 moved technique history from a JSON array to one JSON object per line.
+    fn migrate_v2_to_v3(&self) -> anyhow::Result<()> {
+        let legacy = self.path(LEGACY_TECHNIQUE_HISTORY_FILE);
+        if !legacy.exists() {
+            return Ok(());
+        }
+
+        let data = self.read(&legacy)?;
+        let history: Vec<TechniquePractice> = serde_json::from_str(&data).unwrap_or_default();
+        let mut lines = String::new();
+        for practice in &history {
+            lines.push_str(&serde_json::to_string(practice)?);
+            lines.push('\n');
+        }
+        self.write(self.path(TECHNIQUE_HISTORY_FILE), lines)?;
+        self.remove(legacy)?;
+        Ok(())
+    }
+
+    /// Version 5 keeps a list of sleep sessions per day, so naps can be logged next to the night.
+    fn migrate_v4_to_v5(&self) -> anyhow::Result<()> {
+        if self.path(DAILY_LOG_FILE).exists() {
+            self.save_daily_logs(&self.daily_logs()?)?;
+        }
+        Ok(())
+    }
+
+    /// Rewrites every stored date as `YYYY-MM-DD`, recovering other common layouts. A dream or
+    /// log date that can't be recovered stops the upgrade so it can be fixed by hand; optional
+    /// dates and derived records with one are dropped instead.
+    fn repair_dates(&self) -> anyhow::Result<()> {
+        use serde_json::Value;
+
+        let mut dreams: Value = self.load_json(DREAMS_FILE)?;
+        if let Value::Array(items) = &mut dreams {
+            let mut changed = false;
+            for dream in items.iter_mut() {
+                let context = format!("{} (dream #{})", DREAMS_FILE, dream["id"]);
+                changed |= repair_date_field(dream, "date", Some(&context))?;
+            }
+            if changed {
+                self.save_json(DREAMS_FILE, &dreams)?;
+            }
+        }
+
+        let dir = self.path(DREAMS_DIR);
+        if dir.exists() {
+            for entry in self.list(dir)? {
+                let path = entry?.path();
+                if path.extension().is_some_and(|ext| ext == "md") {
+                    let mut fields = Value::Object(parse_dream_fields(&self.read(&path)?)?);
+                    if repair_date_field(&mut fields, "date", Some(&path.display().to_string()))? {
+                        self.write(&path, dream_file(&serde_json::from_value(fields)?)?)?;
+                    }
+                }
+            }
+        }
+
+        let mut logs: Value = self.load_json(DAILY_LOG_FILE)?;
+        if let Value::Array(items) = &mut logs {
+            let mut changed = false;
+            for log in items.iter_mut() {
+                let context = format!("{} (entry {})", DAILY_LOG_FILE, log["date"]);
+                changed |= repair_date_field(log, "date", Some(&context))?;
+                for nested in ["dream", "sleep", "technique_practice"] {
+                    if let Some(nested) = log.get_mut(nested) {
+                        changed |= repair_date_field(nested, "date", Some(&context))?;
+                    }
+                }
+            }
+            if changed {
+                self.save_json(DAILY_LOG_FILE, &logs)?;
+            }
+        }
+
+        let mut alarms: Value = self.load_json(ALARMS_FILE)?;
+        if let Value::Array(items) = &mut alarms {
+            let mut changed = false;
+            for alarm in items.iter_mut() {
+                changed |= repair_date_field(alarm, "last_triggered", None)?;
+            }
+            if changed {
+                self.save_json(ALARMS_FILE, &alarms)?;
+            }
+        }
+
+        let mut techniques: Value = self.load_json(TECHNIQUES_FILE)?;
+        if let Value::Object(items) = &mut techniques {
+            let mut changed = false;
+            for technique in items.values_mut() {
+                changed |= repair_date_field(technique, "last_practiced", None)?;
+            }
+            if changed {
+                self.save_json(TECHNIQUES_FILE, &techniques)?;
+            }
+        }
+
+        // Technique statistics are recomputed by every report, so unreadable ones are dropped.
+        let mut stats: Value = self.load_json(STATS_FILE)?;
+        if let Some(Value::Object(items)) = stats.get_mut("technique_effectiveness") {
+            let before = items.clone();
+            items.retain(|_, entry| repair_date_field(entry, "last_practiced", None).is_ok() && !entry["last_practiced"].is_null());
+            if *items != before {
+                self.save_json(STATS_FILE, &stats)?;
+            }
+        }
+
+        let mut legacy: Value = self.load_json(LEGACY_TECHNIQUE_HISTORY_FILE)?;
+        if let Value::Array(items) = &mut legacy {
+            let before = items.clone();
+            items.retain_mut(|practice| repair_date_field(practice, "date", None).is_ok() && !practice["date"].is_null());
+            if *items != before {
+                self.save_json(LEGACY_TECHNIQUE_HISTORY_FILE, &legacy)?;
+            }
+        }
+
+        let history = self.path(TECHNIQUE_HISTORY_FILE);
+        if history.exists() {
+            let mut lines = String::new();
+            let mut changed = false;
+            for line in self.read(&history)?.lines() {
+                let Ok(mut practice) = serde_json::from_str::<Value>(line) else {
+                    lines.push_str(line);
+                    lines.push('\n');
+                    continue;
+                };
+                if repair_date_field(&mut practice, "date", None)? {
+                    changed = true;
+                    if practice["date"].is_null() {
+                        continue;
+                    }
+                    lines.push_str(&serde_json::to_string(&practice)?);
+                } else {
+                    lines.push_str(line);
+                }
+                lines.push('\n');
+            }
+            if changed {
+                self.write(history, lines)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+
+    fn load_json<T: DeserializeOwned + Default>(&self, file: &str) -> anyhow::Result<T> {
+        let path = self.path(file);
+        if !path.exists() {
+            self.trace("missing", &[&path]);
+            return Ok(T::default());
+        }
+        let data = self.read(path)?;
+        serde_json::from_str(&data).map_err(|e| JournalError::Corrupt(format!("{}: {}", file, e)).into())
+    }
+
+    /// Writes through a temporary file and a rename, so a crash never leaves a half-written file.
+    fn save_json<T: Serialize + ?Sized>(&self, file: &str, value: &T) -> anyhow::Result<()> {
+        let data = serde_json::to_string_pretty(value)?;
+        let tmp = self.path(&format!("{}.tmp", file));
+        self.write(&tmp, data)?;
+        self.rename(tmp, self.path(file))?;
+        Ok(())
+    }
+
+    pub fn dreams(&self) -> anyhow::Result<Vec<Dream>> {
+        self.load_dreams_from(self.config()?.dream_storage)
+    }
+
+    pub fn save_dreams(&self, dreams: &[Dream]) -> anyhow::Result<()> {
+        self.save_dreams_to(self.config()?.dream_storage, dreams)
+    }
+
+    fn load_dreams_from(&self, storage: DreamStorage) -> anyhow::Result<Vec<Dream>> {
+        match storage {
+            DreamStorage::Json => self.load_json(DREAMS_FILE),
+            DreamStorage::Files => {
+                let dir = self.path(DREAMS_DIR);
+                if !dir.exists() {
+                    return Ok(Vec::new());
+                }
+                let mut dreams = Vec::new();
+                for entry in self.list(dir)? {
+                    let path = entry?.path();
+                    if path.extension().is_some_and(|ext| ext == "md") {
+                        let text = self.read(&path)?;
+                        dreams.push(parse_dream_file(&text)
+                            .map_err(|e| JournalError::Corrupt(format!("{}: {}", path.display(), e)))?);
+                    }
+                }
+                dreams.sort_by_key(|d| d.id);
+                Ok(dreams)
+            }
+        }
+    }
+
+    fn save_dreams_to(&self, storage: DreamStorage, dreams: &[Dream]) -> anyhow::Result<()> {
+        match storage {
+            DreamStorage::Json => self.save_json(DREAMS_FILE, dreams),
+            DreamStorage::Files => {
+                let dir = self.path(DREAMS_DIR);
+                self.create_dir(&dir)?;
+                let mut written = HashSet::new();
+                for dream in dreams {
+                    let name = format!("{}-{}.md", dream.id, slug(&dream.title));
+                    self.write(dir.join(&name), dream_file(dream)?)?;
+                    written.insert(name);
+                }
+                // Files for deleted or renamed dreams would otherwise be loaded again.
+                for entry in self.list(&dir)? {
+                    let entry = entry?;
+                    let name = entry.file_name().to_string_lossy().into_owned();
+                    if name.ends_with(".md") && !written.contains(&name) {
+                        self.remove(entry.path())?;
+                    }
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Moves every dream to the `to` layout, records it in `config.json` and removes the old copy.
+    /// Returns how many dreams were moved.
+    pub fn migrate_dream_storage(&self, to: DreamStorage) -> anyhow::Result<usize> {
+        let from = self.config()?.dream_storage;
+        if from == to {
+            anyhow::bail!("Dreams are already stored in that layout");
+        }
+
+        let dreams = self.load_dreams_from(from)?;
+        self.save_dreams_to(to, &dreams)?;
+        self.set_config_value("dream_storage", serde_json::to_value(to)?)?;
+
+        match from {
+            DreamStorage::Json => {
+                if self.path(DREAMS_FILE).exists() {
+                    self.remove(self.path(DREAMS_FILE))?;
+                }
+            }
+            DreamStorage::Files => self.remove_dir(self.path(DREAMS_DIR))?,
+        }
+        Ok(dreams.len())
+    }
+
+    /// Removes the dreams with the given ids, keeping a snapshot for `undo`. Returns how many were removed.
+    pub fn delete_dreams(&self, ids: &[u32], operation: &str) -> anyhow::Result<usize> {
+        let mut dreams = self.dreams()?;
+        let original = dreams.clone();
+        dreams.retain(|d| !ids.contains(&d.id));
+        let removed = original.len() - dreams.len();
+        for dream in &mut dreams {
+            dream.links.retain(|link| !ids.contains(link));
+        }
+
+        if removed > 0 {
+            // Daily logs keep their own copy of the night's dream, which would otherwise
+            // point at a dream that no longer exists.
+            let mut logs = self.daily_logs()?;
+            let mut logs_changed = false;
+            for log in &mut logs {
+                if log.dream.as_ref().is_some_and(|d| ids.contains(&d.id)) {
+                    log.dream = None;
+                    logs_changed = true;
+                }
+            }
+
+            self.save_json(&format!("{}{}", DREAMS_FILE, UNDO_SUFFIX), &original)?;
+            let also: &[&str] = if logs_changed { &[DAILY_LOG_FILE] } else { &[] };
+            self.snapshot(DREAMS_FILE, also, operation)?;
+            self.save_dreams(&dreams)?;
+            if logs_changed {
+                self.save_daily_logs(&logs)?;
+            }
+            self.rebuild_statistics()?;
+        }
+        Ok(removed)
+    }
+
+    fn snapshot(&self, file: &str, also: &[&str], operation: &str) -> anyhow::Result<()> {
+        for name in std::iter::once(&file).chain(also) {
+            let path = self.path(name);
+            if path.exists() && *name != DREAMS_FILE {
+                self.copy(&path, self.path(&format!("{}{}", name, UNDO_SUFFIX)))?;
+            }
+        }
+        self.save_json(UNDO_FILE, &UndoRecord {
+            file: file.to_string(),
+            operation: operation.to_string(),
+            created: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            also: also.iter().map(|name| name.to_string()).collect(),
+        })
+    }
+
+    /// The marker `report` left on its last `--since-last` run, if it has had one.
+    pub fn report_marker(&self, report: &str) -> anyhow::Result<Option<ReportMarker>> {
+        Ok(self.report_markers()?.remove(report))
+    }
+
+    /// Records that `report` ran now and covered every dream so far.
+    pub fn mark_report(&self, report: &str) -> anyhow::Result<()> {
+        let mut markers = self.report_markers()?;
+        let last_dream_id = self.dreams()?.iter().map(|d| d.id).max().unwrap_or(0);
+        markers.insert(report.to_string(), ReportMarker { at: Utc::now(), last_dream_id });
+        self.save_json(REPORT_MARKERS_FILE, &markers)
+    }
+
+    fn report_markers(&self) -> anyhow::Result<HashMap<String, ReportMarker>> {
+        let path = self.path(REPORT_MARKERS_FILE);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        Ok(serde_json::from_str(&self.read(path)?)?)
+    }
+
+    /// Restores the file touched by the most recent destructive operation, if any.
+    pub fn undo(&self) -> anyhow::Result<Option<UndoRecord>> {
+        let record_path = self.path(UNDO_FILE);
+        if !record_path.exists() {
+            return Ok(None);
+        }
+        let record: UndoRecord = serde_json::from_str(&self.read(&record_path)?)?;
+
+        let snapshot = self.path(&format!("{}{}", record.file, UNDO_SUFFIX));
+        if !snapshot.exists() {
+            anyhow::bail!("Undo snapshot for {} is missing", record.file);
+        }
+        if record.file == DREAMS_FILE {
+            let dreams: Vec<Dream> = serde_json::from_str(&self.read(&snapshot)?)?;
+            self.save_dreams(&dreams)?;
+            self.remove(snapshot)?;
+            self.rebuild_statistics()?;
+        } else {
+            self.rename(snapshot, self.path(&record.file))?;
+        }
+        for file in &record.also {
+            let snapshot = self.path(&format!("{}{}", file, UNDO_SUFFIX));
+            if snapshot.exists() {
+                self.rename(snapshot, self.path(file))?;
+            }
+        }
+        self.remove(record_path)?;
+        Ok(Some(record))
+    }
+
+    /// Links two existing dreams to each other.
+    pub fn link_dreams(&self, id: u32, other_id: u32) -> anyhow::Result<()> {
+        if id == other_id {
+            anyhow::bail!(JournalError::InvalidInput("A dream can't be linked to itself".to_string()));
+        }
+        let mut dreams = self.dreams()?;
+        for missing in [id, other_id] {
+            if !dreams.iter().any(|d| d.id == missing) {
+                anyhow::bail!(JournalError::NotFound(format!("Dream #{} not found", missing)));
+            }
+        }
+
+        for dream in &mut dreams {
+            let target = if dream.id == id {
+                other_id
+            } else if dream.id == other_id {
+                id
+            } else {
+                continue;
+            };
+            if !dream.links.contains(&target) {
+                dream.links.push(target);
+            }
+        }
+        self.save_dreams(&dreams)
+    }
+
+    /// Applies `change` to dream `id` and to its copy in any daily log, then saves both.
+    fn update_dream(&self, id: u32, change: impl Fn(&mut Dream)) -> anyhow::Result<Dream> {
+        let mut dreams = self.dreams()?;
+        let dream = dreams.iter_mut()
+            .find(|d| d.id == id)
+            .ok_or_else(|| JournalError::NotFound(format!("Dream #{} not found", id)))?;
+        change(dream);
+        let updated = dream.clone();
+        self.save_dreams(&dreams)?;
+
+        let mut logs = self.daily_logs()?;
+        let mut logs_changed = false;
+        for dream in logs.iter_mut().filter_map(|log| log.dream.as_mut()).filter(|d| d.id == id) {
+            change(dream);
+            logs_changed = true;
+        }
+        if logs_changed {
+            self.save_daily_logs(&logs)?;
+        }
+        Ok(updated)
+    }
+
+    /// Stars or unstars a dream.
+    pub fn set_dream_starred(&self, id: u32, starred: bool) -> anyhow::Result<Dream> {
+        self.update_dream(id, |dream| dream.starred = starred)
+    }
+
+    /// Replaces tag `from` (with or without the `#`, in any case) by `to` on every dream that has it,
+    /// without repeating `to` where it's already there; an empty `to` just removes the tag.
+    /// Returns the ids of the affected dreams; with `dry_run`, nothing is saved.
+    pub fn retag_dreams(&self, from: &str, to: &str, dry_run: bool) -> anyhow::Result<Vec<u32>> {
+        let same = |tag: &str, other: &str| tag.trim_start_matches('#').eq_ignore_ascii_case(other.trim_start_matches('#'));
+        let (from, to) = (from.trim(), to.trim());
+        if from.trim_start_matches('#').is_empty() {
+            anyhow::bail!(JournalError::InvalidInput("The tag to replace can't be empty".to_string()));
+        }
+
+        let mut dreams = self.dreams()?;
+        let mut affected = Vec::new();
+        for dream in &mut dreams {
+            if !dream.tags.iter().any(|tag| same(tag, from)) {
+                continue;
+            }
+            affected.push(dream.id);
+            let had_target = !to.is_empty() && dream.tags.iter().any(|tag| same(tag, to) && !same(tag, from));
+            let mut replaced = false;
+            dream.tags.retain_mut(|tag| {
+                if !same(tag, from) {
+                    return true;
+                }
+                if to.is_empty() || had_target || replaced {
+                    return false;
+                }
+                *tag = to.to_string();
+                replaced = true;
+                true
+            });
+        }
+
+        if !dry_run && !affected.is_empty() {
+            self.save_dreams(&dreams)?;
+            self.update_statistics(&[])?;
+        }
+        Ok(affected)
+    }
+
+    /// Marks a dream lucid or not, keeping its `#lucid` tag and any daily-log copy in step.
+    pub fn set_dream_lucid(&self, id: u32, lucid: bool) -> anyhow::Result<Dream> {
+        let updated = self.update_dream(id, |dream| {
+            dream.lucid = Some(lucid);
+            dream.tags.retain(|tag| !tag.trim_start_matches('#').eq_ignore_ascii_case("lucid"));
+            if lucid {
+                dream.tags.push("#lucid".to_string());
+            }
+        })?;
+        self.update_statistics(&[])?;
+        Ok(updated)
+    }
+
+    /// Marks the WBTB alarm used on the night of lucid dream `dream_id` as successful, inferred
+    /// from the dream. Returns the alarm's id when one was newly marked.
+    pub fn infer_alarm_success(&self, dream_id: u32) -> anyhow::Result<Option<u32>> {
+        let logs = self.daily_logs()?;
+        let Some(alarm_id) = logs.iter()
+            .filter(|log| log.dream.as_ref().is_some_and(|d| d.id == dream_id && d.lucid == Some(true)))
+            .find_map(|log| log.wbtb_alarm_used) else {
+            return Ok(None);
+        };
+
+        let mut alarms = self.alarms()?;
+        let Some(alarm) = alarms.iter_mut().find(|a| a.id == alarm_id && a.success != Some(true)) else {
+            return Ok(None);
+        };
+        alarm.success = Some(true);
+        alarm.success_inferred = true;
+        self.save_alarms(&alarms)?;
+        Ok(Some(alarm_id))
+    }
+
+    /// Adds later-recalled `text` to the end of a dream under a timestamped separator.
+    pub fn append_to_dream(&self, id: u32, text: &str) -> anyhow::Result<Dream> {
+        let text = text.trim();
+        if text.is_empty() {
+            anyhow::bail!(JournalError::InvalidInput("Nothing to append".to_string()));
+        }
+        let separator = format!("--- (added {}) ---", Local::now().format("%H:%M"));
+        let updated = self.update_dream(id, |dream| {
+            dream.content = format!("{}\n\n{}\n{}", dream.content.trim_end(), separator, text);
+        })?;
+        self.update_statistics(&[Dream { content: text.to_string(), ..updated.clone() }])?;
+        Ok(updated)
+    }
+
+    pub fn next_dream_id(&self) -> anyhow::Result<u32> {
+        Ok(next_id(&self.dreams()?))
+    }
+
+    /// Stores `dream` under the next free id and refreshes the statistics.
+    pub fn add_dream(&self, dream: Dream) -> anyhow::Result<Dream> {
+        Ok(self.add_dreams(vec![dream])?.remove(0))
+    }
+
+    /// Stores several dreams at once, giving each the next free id, and refreshes the statistics.
+    pub fn add_dreams(&self, mut new: Vec<Dream>) -> anyhow::Result<Vec<Dream>> {
+        if new.iter().any(|d| d.title.trim().is_empty() || d.content.trim().is_empty()) {
+            anyhow::bail!(JournalError::InvalidInput("Dream title and content can't be empty".to_string()));
+        }
+
+        let mut dreams = self.dreams()?;
+        for dream in &mut new {
+            dream.id = next_id(&dreams);
+            dreams.push(dream.clone());
+        }
+        self.save_dreams(&dreams)?;
+        self.update_statistics(&new)?;
+        Ok(new)
+    }
+
+    pub fn daily_logs(&self) -> anyhow::Result<Vec<DailyLog>> {
+        self.load_json(DAILY_LOG_FILE)
+    }
+
+    pub fn save_daily_logs(&self, logs: &[DailyLog]) -> anyhow::Result<()> {
+        self.save_json(DAILY_LOG_FILE, logs)
+    }
+
+    /// Merges daily logs that share a date (see `merge_duplicate_logs`), saving only if any did.
+    pub fn repair_daily_logs(&self) -> anyhow::Result<Vec<(NaiveDate, usize)>> {
+        let (logs, duplicates) = merge_duplicate_logs(self.daily_logs()?);
+        if !duplicates.is_empty() {
+            self.save_daily_logs(&logs)?;
+        }
+        Ok(duplicates)
+    }
+
+    pub fn alarms(&self) -> anyhow::Result<Vec<WBTBAlarm>> {
+        self.load_json(ALARMS_FILE)
+    }
+
+    pub fn save_alarms(&self, alarms: &[WBTBAlarm]) -> anyhow::Result<()> {
+        self.save_json(ALARMS_FILE, alarms)
+    }
+
+    /// Hands out a new alarm id. Ids are never reused, even after the alarm that had one is
+    /// canceled, so a scheduler still waiting on a canceled alarm can't fire its successor.
+    pub fn next_alarm_id(&self) -> anyhow::Result<u32> {
+        let path = self.path(LAST_ALARM_ID_FILE);
+        let stored: u32 = if path.exists() { serde_json::from_str(&self.read(path)?)? } else { 0 };
+        let highest = self.alarms()?.iter().map(|a| a.id)
+            .chain(self.alarm_log()?.iter().map(|e| e.alarm_id))
+            .max()
+            .unwrap_or(0);
+        let id = stored.max(highest) + 1;
+        self.save_json(LAST_ALARM_ID_FILE, &id)?;
+        Ok(id)
+    }
+
+    fn load_jsonl<T: DeserializeOwned>(&self, file: &str) -> anyhow::Result<Vec<T>> {
+        let path = self.path(file);
+        if !path.exists() {
+            self.trace("missing", &[&path]);
+            return Ok(Vec::new());
+        }
+
+        // A line that doesn't parse, such as one cut short by a crash, costs only that record.
+        let data = self.read(path)?;
+        Ok(data.lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect())
+    }
+
+    /// Appends one record as a line, without rewriting earlier records.
+    fn append_jsonl<T: Serialize>(&self, file: &str, record: &T) -> anyhow::Result<()> {
+        let path = self.path(file);
+        let needs_newline = path.exists()
+            && self.read(&path).is_ok_and(|data| data.bytes().last().is_some_and(|b| b != b'\n'));
+
+        let mut line = serde_json::to_string(record)?;
+        if needs_newline {
+            line.insert(0, '\n');
+        }
+        line.push('\n');
+
+        self.trace("append", &[&path]);
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(path)?;
+        file.write_all(line.as_bytes())?;
+        Ok(())
+    }
+
+    pub fn technique_history(&self) -> anyhow::Result<Vec<TechniquePractice>> {
+        self.load_jsonl(TECHNIQUE_HISTORY_FILE)
+    }
+
+    pub fn record_technique_practice(&self, technique: &str, outcome: TechniqueOutcome, duration_minutes: u32) -> anyhow::Result<()> {
+        self.append_jsonl(TECHNIQUE_HISTORY_FILE, &TechniquePractice {
+            technique: technique.to_string(),
+            date: Utc::now().date_naive(),
+            duration_minutes,
+            outcome,
+        })
+    }
+
+    pub fn reality_check_log(&self) -> anyhow::Result<Vec<RealityCheckRecord>> {
+        self.load_jsonl(REALITY_CHECK_LOG_FILE)
+    }
+
+    pub fn record_reality_check(&self, prompt: &str, aware: bool, anchor: Option<&str>) -> anyhow::Result<()> {
+        self.append_jsonl(REALITY_CHECK_LOG_FILE, &RealityCheckRecord {
+            timestamp: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            prompt: prompt.to_string(),
+            aware,
+            anchor: anchor.map(str::to_string),
+        })
+    }
+
+    pub fn alarm_log(&self) -> anyhow::Result<Vec<AlarmEvent>> {
+        self.load_jsonl(ALARM_LOG_FILE)
+    }
+
+    pub fn record_alarm_event(&self, alarm_id: u32, kind: AlarmEventKind, detail: &str) -> anyhow::Result<()> {
+        self.append_jsonl(ALARM_LOG_FILE, &AlarmEvent {
+            timestamp: Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            alarm_id,
+            kind,
+            detail: detail.to_string(),
+        })
+    }
+
+    pub fn techniques(&self) -> anyhow::Result<HashMap<String, TechniqueData>> {
+        let path = self.path(TECHNIQUES_FILE);
+        if path.exists() {
+            let data = self.read(path)?;
+            return Ok(serde_json::from_str(&data)?);
+        }
+
+        Ok(default_techniques())
+    }
+
+    pub fn save_techniques(&self, techniques: &HashMap<String, TechniqueData>) -> anyhow::Result<()> {
+        self.save_json(TECHNIQUES_FILE, techniques)
+    }
+
+    pub fn config(&self) -> anyhow::Result<Config> {
+        let mut config: Config = self.load_json(CONFIG_FILE)?;
+
+        let prompts_path = self.path(PROMPTS_FILE);
+        if prompts_path.exists() {
+            config.reality_check_prompts.extend(self.read(prompts_path)?
+                .lines()
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+                .map(RealityCheckPrompt::Plain));
+        }
+
+        Ok(config)
+    }
+
+    /// Loads every data file and reports what's broken, without changing anything.
+    pub fn verify(&self) -> Vec<FileReport> {
+        fn check<T>(report: &mut FileReport, loaded: anyhow::Result<T>) -> Option<T> {
+            loaded.map_err(|e| report.problems.push(format!("can't be parsed: {}", e))).ok()
+        }
+
+        let mut config_report = FileReport::new(CONFIG_FILE);
+        let storage = check(&mut config_report, self.config())
+            .map(|c| c.dream_storage)
+            .unwrap_or_default();
+
+        let mut dreams_report = FileReport::new(match storage {
+            DreamStorage::Json => DREAMS_FILE,
+            DreamStorage::Files => DREAMS_DIR,
+        });
+        let dreams = check(&mut dreams_report, self.load_dreams_from(storage)).unwrap_or_default();
+        let mut seen = HashSet::new();
+        for dream in &dreams {
+            if !seen.insert(dream.id) {
+                dreams_report.problems.push(format!("duplicate dream id #{}", dream.id));
+            }
+        }
+
+        let mut logs_report = FileReport::new(DAILY_LOG_FILE);
+        let mut log_dates = HashSet::new();
+        for log in check(&mut logs_report, self.daily_logs()).unwrap_or_default() {
+            if !log_dates.insert(log.date) {
+                logs_report.problems.push(format!("more than one log for {} (merge them with `daily repair`)", log.date));
+            }
+            if let Some(dream) = &log.dream
+                && !seen.contains(&dream.id) {
+                logs_report.problems.push(format!("log {} refers to missing dream #{}", log.date, dream.id));
+            }
+        }
+
+        let mut alarms_report = FileReport::new(ALARMS_FILE);
+        for alarm in check(&mut alarms_report, self.alarms()).unwrap_or_default() {
+            for (label, time) in [("bedtime", &alarm.bedtime), ("wake time", &alarm.wake_time)] {
+                if NaiveTime::parse_from_str(time, "%H:%M").is_err() {
+                    alarms_report.problems.push(format!("alarm #{} has an invalid {} '{}'", alarm.id, label, time));
+                }
+            }
+        }
+
+        let mut history_report = FileReport::new(TECHNIQUE_HISTORY_FILE);
+        if let Ok(data) = self.read(self.path(TECHNIQUE_HISTORY_FILE)) {
+            for (number, line) in data.lines().enumerate() {
+                if let Err(e) = serde_json::from_str::<TechniquePractice>(line) {
+                    history_report.problems.push(format!("line {} can't be parsed: {}", number + 1, e));
+                }
+            }
+        }
+
+        let mut techniques_report = FileReport::new(TECHNIQUES_FILE);
+        check(&mut techniques_report, self.techniques());
+        let mut stats_report = FileReport::new(STATS_FILE);
+        check(&mut stats_report, self.stats());
+
+        vec![
+            dreams_report,
+            logs_report,
+            alarms_report,
+            history_report,
+            techniques_report,
+            stats_report,
+            config_report,
+        ]
+    }
+
+    /// Sets one top-level key in `config.json`, leaving the rest of the file as written.
+    /// Unknown keys and values that don't fit the setting are rejected.
+    pub fn set_config_value(&self, key: &str, value: serde_json::Value) -> anyhow::Result<()> {
+        let serde_json::Value::Object(defaults) = serde_json::to_value(Config::default())? else {
+            unreachable!();
+        };
+        if !defaults.contains_key(key) {
+            let known: Vec<&str> = defaults.keys().map(String::as_str).collect();
+            anyhow::bail!(JournalError::InvalidInput(format!("Unknown setting '{}'. Known settings: {}", key, known.join(", "))));
+        }
+
+        let mut config: serde_json::Map<String, serde_json::Value> = self.load_json(CONFIG_FILE)?;
+        config.insert(key.to_string(), value);
+        serde_json::from_value::<Config>(serde_json::Value::Object(config.clone()))
+            .map_err(|e| JournalError::InvalidInput(format!("Invalid value for {}: {}", key, e)))?;
+        self.save_json(CONFIG_FILE, &config)
+    }
+
+    /// Whether extra reality check prompts are being read from `prompts.txt`.
+    pub fn has_prompts_file(&self) -> bool {
+        self.path(PROMPTS_FILE).exists()
+    }
+
+    pub fn stats(&self) -> anyhow::Result<Statistics> {
+        self.load_json(STATS_FILE)
+    }
+
+    pub fn save_stats(&self, stats: &Statistics) -> anyhow::Result<()> {
+        self.save_json(STATS_FILE, stats)
+    }
+
+    /// Refreshes totals and the calendar, merging only the words of `delta` (newly added dreams).
+    /// Words that fell out of the kept top `COMMON_WORDS_KEPT` restart from zero; `rebuild_statistics` recounts them.
+    pub fn update_statistics(&self, delta: &[Dream]) -> anyhow::Result<()> {
+        let stats = self.stats()?;
+        self.refresh_statistics(stats, delta, &self.dreams()?)
+    }
+
+    /// Recomputes every statistic from scratch, e.g. after dreams were removed.
+    pub fn rebuild_statistics(&self) -> anyhow::Result<()> {
+        let mut stats = self.stats()?;
+        stats.common_words.clear();
+        let dreams = self.dreams()?;
+        self.refresh_statistics(stats, &dreams, &dreams)
+    }
+
+    fn refresh_statistics(&self, mut stats: Statistics, delta: &[Dream], dreams: &[Dream]) -> anyhow::Result<()> {
+        stats.total_dreams = dreams.len() as u32;
+        stats.lucid_dreams = dreams.iter()
+            .filter(|d| d.tags.contains(&"#lucid".to_string()))
+            .count() as u32;
+
+        for dream in delta {
+            for word in tokenize(&dream.content) {
+                *stats.common_words.entry(word).or_insert(0) += 1;
+            }
+        }
+        if stats.common_words.len() > COMMON_WORDS_KEPT {
+            let mut words: Vec<(String, u32)> = stats.common_words.drain().collect();
+            words.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            words.truncate(COMMON_WORDS_KEPT);
+            stats.common_words = words.into_iter().collect();
+        }
+
+        stats.dream_calendar.clear();
+        for dream in dreams {
+            let date = dream.date.format(DATE_FORMAT).to_string();
+            *stats.dream_calendar.entry(date).or_insert(0) += 1;
+        }
+
+        self.save_stats(&stats)
+    }
+
+    pub fn technique_effectiveness(&self) -> anyhow::Result<HashMap<String, TechniqueStats>> {
+        Ok(effectiveness_from(&self.technique_history()?, &self.daily_logs()?, &self.alarms()?, Utc::now().date_naive()))
+    }
+}
+
+const SLEEP_CONDITION: &str = "Sleep before practice: ~";
+const AWAKE_CONDITION: &str = "WBTB awake period: ";
+
+/// The id after the highest one in `dreams`, or 1 for an empty journal.
+fn next_id(dreams: &[Dream]) -> u32 {
+    dreams.iter().map(|d| d.id).max().map_or(1, |id| id + 1)
+}
+
+/// Splits a plaintext diary into `(date, title, content)` entries. A section starts at a
+/// date header line such as `2022-03-14:`; its first non-empty line is the title and the rest
+/// the content. Returns the entries and how many sections were skipped for a bad date or no text.
+pub fn parse_diary(text: &str) -> (Vec<(NaiveDate, String, String)>, usize) {
+    fn header(line: &str) -> Option<&str> {
+        let label = line.trim().strip_suffix(':')?;
+        (label.starts_with(|c: char| c.is_ascii_digit()) && !label.contains(char::is_whitespace)).then_some(label)
+    }
+
+    let mut sections: Vec<(&str, Vec<&str>)> = Vec::new();
+    for line in text.lines() {
+        match header(line) {
+            Some(label) => sections.push((label, Vec::new())),
+            None => {
+                if let Some((_, body)) = sections.last_mut() {
+                    body.push(line);
+                }
+            }
+        }
+    }
+
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    for (label, body) in sections {
+        let mut lines = body.into_iter().skip_while(|l| l.trim().is_empty());
+        let title = lines.next().unwrap_or_default().trim().to_string();
+        let content = lines.collect::<Vec<_>>().join("\n").trim().to_string();
+        match parse_date(label) {
+            Some(date) if !title.is_empty() => {
+                let content = if content.is_empty() { title.clone() } else { content };
+                entries.push((date, title, content));
+            }
+            _ => skipped += 1,
+        }
+    }
+    (entries, skipped)
+}
+
+/// Lowercase, dash-separated form of a title for use in file names.
+fn slug(title: &str) -> String {
+    let words = tokenize(title);
+    let mut slug = String::new();
+    for word in words {
+        if slug.len() + word.len() > 40 {
+            break;
+        }
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&word);
+    }
+    if slug.is_empty() { "dream".to_string() } else { slug }
+}
+
+/// Renders a dream as markdown with YAML front-matter. Each value is written as JSON,
+/// which YAML reads as-is, so the front-matter round-trips without a YAML parser.
+fn dream_file(dream: &Dream) -> anyhow::Result<String> {
+    let serde_json::Value::Object(fields) = serde_json::to_value(dream)? else {
+        unreachable!();
+    };
+    let mut text = String::from("---\n");
+    for (key, value) in fields.iter().filter(|(key, _)| *key != "content") {
+        text.push_str(&format!("{}: {}\n", key, value));
+    }
+    text.push_str("---\n\n");
+    text.push_str(&dream.content);
+    text.push('\n');
+    Ok(text)
+}
+
+fn parse_dream_file(text: &str) -> anyhow::Result<Dream> {
+    Ok(serde_json::from_value(serde_json::Value::Object(parse_dream_fields(text)?))?)
+}
+
+fn parse_dream_fields(text: &str) -> anyhow::Result<serde_json::Map<String, serde_json::Value>> {
+    let rest = text.strip_prefix("---\n")
+        .ok_or_else(|| anyhow::anyhow!("missing front-matter"))?;
+    let (header, body) = rest.split_once("\n---\n")
+        .ok_or_else(|| anyhow::anyhow!("unterminated front-matter"))?;
+
+    let mut fields = serde_json::Map::new();
+    for line in header.lines().filter(|l| !l.trim().is_empty()) {
+        let (key, value) = line.split_once(':')
+            .ok_or_else(|| anyhow::anyhow!("bad front-matter line '{}'", line))?;
+        fields.insert(key.trim().to_string(), serde_json::from_str(value.trim())?);
+    }
+    fields.insert("content".to_string(), body.trim().to_string().into());
+    Ok(fields)
+}
+
+/// Parses a date written in `DATE_FORMAT` or one of the layouts older journals or hand edits
+/// tend to contain: other separators, day-first, or a full timestamp.
+fn recover_date(text: &str) -> Option<NaiveDate> {
+    let text = text.trim();
+    ["%Y-%m-%d", "%Y/%m/%d", "%Y.%m.%d", "%d.%m.%Y", "%d/%m/%Y"].iter()
+        .find_map(|format| NaiveDate::parse_from_str(text, format).ok())
+        .or_else(|| DateTime::parse_from_rfc3339(text).ok().map(|dt| dt.date_naive()))
+        .or_else(|| text.get(..10).and_then(|prefix| NaiveDate::parse_from_str(prefix, DATE_FORMAT).ok()))
+}
+
+/// Normalizes the date string at `value[key]`, returning whether it changed. An unrecoverable
+/// date is an error naming `required_in` when given, and becomes `null` otherwise.
+fn repair_date_field(value: &mut serde_json::Value, key: &str, required_in: Option<&str>) -> anyhow::Result<bool> {
+    let Some(serde_json::Value::String(text)) = value.get(key) else {
+        return Ok(false);
+    };
+    let repaired = match (recover_date(text), required_in) {
+        (Some(date), _) => serde_json::Value::String(date.format(DATE_FORMAT).to_string()),
+        (None, Some(context)) => anyhow::bail!(JournalError::Corrupt(format!(
+            "Can't read the date '{}' in {}; change it to YYYY-MM-DD and run again", text, context))),
+        (None, None) => serde_json::Value::Null,
+    };
+    if value[key] == repaired {
+        return Ok(false);
+    }
+    value[key] = repaired;
+    Ok(true)
+}
+
+/// Days after which a session counts half as much towards the recency-weighted success rate.
+pub const RECENCY_HALF_LIFE_DAYS: f32 = 30.0;
+
+/// Success rates per technique. `optimal_conditions` holds, for each sleep length and
+/// WBTB awake period seen on the day of a successful session, the share of successes
+/// that happened under it.
+pub fn effectiveness_from(
+    history: &[TechniquePractice],
+    logs: &[DailyLog],
+    alarms: &[WBTBAlarm],
+    today: NaiveDate,
+) -> HashMap<String, TechniqueStats> {
+    let mut stats: HashMap<String, TechniqueStats> = HashMap::new();
+    let mut weighted: HashMap<&str, (f32, f32)> = HashMap::new();
+
+    for practice in history {
+        let entry = stats.entry(practice.technique.clone()).or_insert_with(|| TechniqueStats {
+            attempts: 0,
+            successes: 0,
+            last_practiced: practice.date,
+            success_rate: 0.0,
+            optimal_conditions: HashMap::new(),
+            weighted_success_rate: 0.0,
+            total_minutes: 0,
+            average_minutes: 0.0,
+        });
+
+        entry.attempts += 1;
+        entry.total_minutes += practice.duration_minutes;
+        entry.average_minutes = entry.total_minutes as f32 / entry.attempts as f32;
+        let age_days = (today - practice.date).num_days().max(0);
+        let weight = 0.5_f32.powf(age_days as f32 / RECENCY_HALF_LIFE_DAYS);
+        let (weighted_successes, weighted_attempts) = weighted.entry(&practice.technique).or_default();
+        *weighted_attempts += weight;
+
+        if practice.outcome.is_success() {
+            entry.successes += 1;
+            *weighted_successes += weight;
+            for condition in practice_conditions(practice.date, logs, alarms) {
+                *entry.optimal_conditions.entry(condition).or_insert(0.0) += 1.0;
+            }
+        }
+
+        if entry.attempts > 0 {
+            entry.success_rate = (entry.successes as f32 / entry.attempts as f32) * 100.0;
+        }
+    }
+
+    for (technique, stat) in stats.iter_mut() {
+        if let Some((successes, attempts)) = weighted.get(technique.as_str())
+            && *attempts > 0.0 {
+            stat.weighted_success_rate = successes / attempts * 100.0;
+        }
+        for value in stat.optimal_conditions.values_mut() {
+            *value = (*value / stat.successes as f32) * 100.0;
+        }
+    }
+
+    stats
+}
+
+/// Sleep length and WBTB awake period recorded for `date`. When a WBTB alarm was used,
+/// the sleep before practice is the alarm's bedtime to wake time rather than the whole night.
+fn practice_conditions(date: NaiveDate, logs: &[DailyLog], alarms: &[WBTBAlarm]) -> Vec<String> {
+    let Some(log) = logs.iter().find(|l| l.date == date) else {
+        return Vec::new();
+    };
+    let alarm = log.wbtb_alarm_used.and_then(|id| alarms.iter().find(|a| a.id == id));
+
+    let slept = match alarm {
+        Some(alarm) => span_minutes(&alarm.bedtime, &alarm.wake_time),
+        None => log.main_sleep().and_then(sleep_minutes),
+    };
+
+    let mut conditions = Vec::new();
+    if let Some(minutes) = slept {
+        conditions.push(format!("{}{}h", SLEEP_CONDITION, (minutes as f32 / 60.0).round()));
+    }
+    if alarm.is_some() && let Some(awake) = log.awake_minutes(alarms) {
+        let low = awake / 10 * 10;
+        conditions.push(format!("{}{}-{} min", AWAKE_CONDITION, low, low + 10));
+    }
+    conditions
+}
+
+/// Turns the most common successful conditions into advice, e.g.
+/// "worked best after ~5h of sleep with a WBTB awake period of 20-30 min".
+pub fn conditions_advice(stats: &TechniqueStats) -> Option<String> {
+    let best = |prefix: &str| {
+        stats.optimal_conditions.iter()
+            .filter_map(|(condition, rate)| condition.strip_prefix(prefix).map(|value| (value, *rate)))
+            .max_by(|a, b| a.1.total_cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(value, _)| value)
+    };
+
+    match (best(SLEEP_CONDITION), best(AWAKE_CONDITION)) {
+        (Some(sleep), Some(awake)) => Some(format!("worked best after ~{} of sleep with a WBTB awake period of {}", sleep, awake)),
+        (Some(sleep), None) => Some(format!("worked best after ~{} of sleep", sleep)),
+        (None, Some(awake)) => Some(format!("worked best with a WBTB awake period of {}", awake)),
+        (None, None) => None,
+    }
+}
+
+/// Control levels of full-lucid sessions in date order, for one technique or all of them.
+pub fn control_levels(history: &[TechniquePractice], technique: Option<&str>) -> Vec<u8> {
+    let mut sessions: Vec<&TechniquePractice> = history.iter()
+        .filter(|practice| technique.is_none_or(|t| practice.technique == t))
+        .collect();
+    sessions.sort_by_key(|s| s.date);
+    sessions.iter()
+        .filter_map(|practice| match practice.outcome {
+            TechniqueOutcome::FullLucid { control_level } => Some(control_level),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Change in average control between the older and newer half of the last `window` levels;
+/// positive means improving. `None` with fewer than two levels to compare.
+pub fn control_trend(levels: &[u8], window: usize) -> Option<f32> {
+    let recent = &levels[levels.len().saturating_sub(window)..];
+    if recent.len() < 2 {
+        return None;
+    }
+    let (older, newer) = recent.split_at(recent.len() / 2);
+    let mean = |half: &[u8]| half.iter().map(|&l| f32::from(l)).sum::<f32>() / half.len() as f32;
+    Some(mean(newer) - mean(older))
+}
+
+/// Weekly reality-check counts next to that week's lucid dreams, oldest week first.
+pub fn reality_check_weeks(
+    logs: &[DailyLog],
+    dreams: &[Dream],
+    checks: &[RealityCheckRecord],
+    week_start: Weekday,
+) -> Vec<RealityCheckWeek> {
+    fn week(weeks: &mut HashMap<NaiveDate, RealityCheckWeek>, date: NaiveDate, week_start: Weekday) -> &mut RealityCheckWeek {
+        let start = date.week(week_start).first_day();
+        weeks.entry(start).or_insert(RealityCheckWeek { start, checks: 0, aware: 0, dreams: 0, lucid: 0 })
+    }
+    let mut weeks = HashMap::new();
+
+    for log in logs {
+        week(&mut weeks, log.date, week_start).checks += log.reality_checks;
+    }
+    for dream in dreams {
+        let entry = week(&mut weeks, dream.date, week_start);
+        entry.dreams += 1;
+        entry.lucid += (dream.lucid == Some(true)) as u32;
+    }
+    for check in checks.iter().filter(|c| c.aware) {
+        if let Some(date) = check.timestamp.get(..10).and_then(parse_date) {
+            week(&mut weeks, date, week_start).aware += 1;
+        }
+    }
+
+    let mut weeks: Vec<RealityCheckWeek> = weeks.into_values().collect();
+    weeks.sort_by_key(|w| w.start);
+    weeks
+}
+
+/// Logged checks per anchor, covering every configured anchor (even unused ones) and any
+/// others found in the log, most used first.
+pub fn anchor_usage(anchors: &[String], checks: &[RealityCheckRecord]) -> Vec<AnchorUsage> {
+    fn entry(usage: &mut Vec<AnchorUsage>, anchor: &str) -> usize {
+        match usage.iter().position(|u| u.anchor.eq_ignore_ascii_case(anchor)) {
+            Some(index) => index,
+            None => {
+                usage.push(AnchorUsage { anchor: anchor.to_string(), checks: 0, aware: 0, last_used: None });
+                usage.len() - 1
+            }
+        }
+    }
+    let mut usage: Vec<AnchorUsage> = Vec::new();
+
+    for anchor in anchors {
+        entry(&mut usage, anchor);
+    }
+    for check in checks {
+        let Some(anchor) = &check.anchor else {
+            continue;
+        };
+        let index = entry(&mut usage, anchor);
+        let used = &mut usage[index];
+        used.checks += 1;
+        used.aware += check.aware as u32;
+        let date = check.timestamp.get(..10).and_then(parse_date);
+        used.last_used = used.last_used.max(date);
+    }
+
+    usage.sort_by(|a, b| b.checks.cmp(&a.checks).then(a.anchor.cmp(&b.anchor)));
+    usage
+}
+
+/// An inclusive span of days, e.g. for comparing two periods in `stats compare`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DateRange {
+    pub start: NaiveDate,
+    pub end: NaiveDate,
+}
+
+impl DateRange {
+    /// Parses `YYYY`, `YYYY-MM`, `YYYY-MM-DD`, `FROM..TO` (days), `this-month`, `last-month`,
+    /// `this-week`, `last-week` (weeks starting on `week_start`) or `last-Nd` (the last N days including today).
+    pub fn parse(text: &str, today: NaiveDate, week_start: Weekday) -> anyhow::Result<DateRange> {
+        let text = text.trim();
+        let month = |year: i32, month: u32| -> Option<DateRange> {
+            let start = NaiveDate::from_ymd_opt(year, month, 1)?;
+            let next = if month == 12 {
+                NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+            } else {
+                NaiveDate::from_ymd_opt(year, month + 1, 1)?
+            };
+            Some(DateRange { start, end: next.pred_opt()? })
+        };
+        let week = |date: NaiveDate| {
+            let week = date.week(week_start);
+            DateRange { start: week.first_day(), end: week.last_day() }
+        };
+
+        let range = match text {
+            "this-month" => month(today.year(), today.month()),
+            "last-month" => month(today.year(), today.month()).and_then(|m| m.start.pred_opt())
+                .and_then(|last| month(last.year(), last.month())),
+            "this-week" => Some(week(today)),
+            "last-week" => Some(week(today - chrono::Duration::days(7))),
+            _ => if let Some((from, to)) = text.split_once("..") {
+                parse_date(from).zip(parse_date(to)).map(|(start, end)| DateRange { start, end })
+            } else if let Some(days) = text.strip_prefix("last-").and_then(|d| d.strip_suffix('d')) {
+                days.parse::<i64>().ok().filter(|d| *d > 0)
+                    .map(|d| DateRange { start: today - chrono::Duration::days(d - 1), end: today })
+            } else if let Some(date) = parse_date(text) {
+                Some(DateRange { start: date, end: date })
+            } else if let Some((year, m)) = text.split_once('-') {
+                year.parse().ok().zip(m.parse().ok()).and_then(|(year, m)| month(year, m))
+            } else {
+                text.parse::<i32>().ok().and_then(|year| Some(DateRange {
+                    start: NaiveDate::from_ymd_opt(year, 1, 1)?,
+                    end: NaiveDate::from_ymd_opt(year, 12, 31)?,
+                }))
+            },
+        };
+
+        match range {
+            Some(range) if range.start <= range.end => Ok(range),
+            Some(_) => anyhow::bail!(JournalError::InvalidInput(format!("Range '{}' ends before it starts", text))),
+            None => anyhow::bail!(JournalError::InvalidInput(format!(
+                "Unrecognised range '{}' (try 2025-09, 2025, this-month, last-30d or 2025-09-01..2025-09-15)", text))),
+        }
+    }
+
+    pub fn contains(&self, date: NaiveDate) -> bool {
+        self.start <= date && date <= self.end
+    }
+
+    /// Days covered, not counting any part of the range that's still in the future.
+    pub fn days(&self, today: NaiveDate) -> i64 {
+        ((self.end.min(today) - self.start).num_days() + 1).max(0)
+    }
+}
+
+/// Headline numbers for one period of the journal.
+#[derive(Debug, Clone, Default)]
+pub struct PeriodSummary {
+    pub days: i64,
+    pub dreams: u32,
+    pub lucid: u32,
+    pub logged_days: u32,
+    pub average_quality: Option<f32>,
+    pub reality_checks: u32,
+}
+
+impl PeriodSummary {
+    pub fn lucid_rate(&self) -> Option<f32> {
+        (self.dreams > 0).then(|| self.lucid as f32 / self.dreams as f32 * 100.0)
+    }
+
+    pub fn dreams_per_week(&self) -> Option<f32> {
+        (self.days > 0).then(|| self.dreams as f32 / self.days as f32 * 7.0)
+    }
+
+    pub fn checks_per_day(&self) -> Option<f32> {
+        (self.logged_days > 0).then(|| self.reality_checks as f32 / self.logged_days as f32)
+    }
+}
+
+pub fn period_summary(dreams: &[Dream], logs: &[DailyLog], range: DateRange, today: NaiveDate) -> PeriodSummary {
+    let dreams: Vec<&Dream> = dreams.iter().filter(|d| range.contains(d.date)).collect();
+    let logs: Vec<&DailyLog> = logs.iter().filter(|l| range.contains(l.date)).collect();
+    let qualities: Vec<f32> = logs.iter().filter_map(|log| log.sleep_quality()).collect();
+
+    PeriodSummary {
+        days: range.days(today),
+        dreams: dreams.len() as u32,
+        lucid: dreams.iter().filter(|d| d.lucid == Some(true)).count() as u32,
+        logged_days: logs.len() as u32,
+        average_quality: (!qualities.is_empty()).then(|| qualities.iter().sum::<f32>() / qualities.len() as f32),
+        reality_checks: logs.iter().map(|log| log.reality_checks).sum(),
+    }
+}
+
+/// How many dreams took place in each setting, and how many of those were lucid.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SettingCount {
+    pub setting: String,
+    pub dreams: u32,
+    pub lucid: u32,
+}
+
+impl SettingCount {
+    pub fn lucid_rate(&self) -> f32 {
+        self.lucid as f32 * 100.0 / self.dreams.max(1) as f32
+    }
+}
+
+/// Dreams per setting, most common first and ties by name. Settings are compared ignoring case.
+pub fn setting_counts(dreams: &[Dream]) -> Vec<SettingCount> {
+    let mut counts: HashMap<String, (u32, u32)> = HashMap::new();
+    for dream in dreams {
+        if let Some(setting) = dream.setting.as_deref().map(|s| s.trim().to_lowercase()).filter(|s| !s.is_empty()) {
+            let entry = counts.entry(setting).or_default();
+            entry.0 += 1;
+            entry.1 += (dream.lucid == Some(true)) as u32;
+        }
+    }
+
+    let mut settings: Vec<SettingCount> = counts.into_iter()
+        .map(|(setting, (dreams, lucid))| SettingCount { setting, dreams, lucid })
+        .collect();
+    settings.sort_by(|a, b| b.dreams.cmp(&a.dreams).then_with(|| a.setting.cmp(&b.setting)));
+    settings
+}
+
+/// Recall vividness of rated dreams against the quality of that day's sleep.
+#[derive(Debug, Clone, Default)]
+pub struct VividnessByQuality {
+    /// `(quality, average_vividness, dreams)` for each rounded sleep quality, lowest first.
+    pub by_quality: Vec<(u8, f32, u32)>,
+    /// Pearson correlation between sleep quality and vividness, once `MIN_CORRELATION_DREAMS` are rated.
+    pub correlation: Option<f32>,
+}
+
+pub fn vividness_by_quality(dreams: &[Dream], logs: &[DailyLog]) -> VividnessByQuality {
+    let pairs: Vec<(f32, f32)> = dreams.iter()
+        .filter_map(|dream| {
+            let quality = logs.iter().find(|log| log.date == dream.date)?.sleep_quality()?;
+            Some((quality, dream.vividness? as f32))
+        })
+        .collect();
+
+    let mut groups: HashMap<u8, (f32, u32)> = HashMap::new();
+    for &(quality, vividness) in &pairs {
+        let entry = groups.entry(quality.round() as u8).or_default();
+        entry.0 += vividness;
+        entry.1 += 1;
+    }
+    let mut by_quality: Vec<(u8, f32, u32)> = groups.into_iter()
+        .map(|(quality, (sum, dreams))| (quality, sum / dreams as f32, dreams))
+        .collect();
+    by_quality.sort_by_key(|(quality, _, _)| *quality);
+
+    let correlation = (pairs.len() >= MIN_CORRELATION_DREAMS).then(|| correlation(&pairs)).flatten();
+    VividnessByQuality { by_quality, correlation }
+}
+
+fn correlation(pairs: &[(f32, f32)]) -> Option<f32> {
+    let n = pairs.len() as f32;
+    let mean_x = pairs.iter().map(|(x, _)| x).sum::<f32>() / n;
+    let mean_y = pairs.iter().map(|(_, y)| y).sum::<f32>() / n;
+    let (mut covariance, mut var_x, mut var_y) = (0.0, 0.0, 0.0);
+    for (x, y) in pairs {
+        covariance += (x - mean_x) * (y - mean_y);
+        var_x += (x - mean_x).powi(2);
+        var_y += (y - mean_y).powi(2);
+    }
+    (var_x > 0.0 && var_y > 0.0).then(|| covariance / (var_x * var_y).sqrt())
+}
+
+/// Width logged WBTB awake periods are rounded to when comparing them, so 28 and 31 minutes count together.
+pub const AWAKE_BUCKET_MINUTES: u32 = 5;
+
+/// Lucid nights out of all logged nights, split by whether a WBTB alarm was used.
+#[derive(Debug, Clone, Default)]
+pub struct WbtbLucidity {
+    pub with_wbtb: (u32, u32),
+    pub without_wbtb: (u32, u32),
+    /// `(awake_minutes, lucid, nights)` for each awake period, shortest first. Periods are the minutes
+    /// actually spent awake when logged (rounded to `AWAKE_BUCKET_MINUTES`), else the alarm's plan.
+    pub by_awake_minutes: Vec<(u32, u32, u32)>,
+}
+
+pub fn wbtb_lucidity(logs: &[DailyLog], alarms: &[WBTBAlarm]) -> WbtbLucidity {
+    let mut result = WbtbLucidity::default();
+    let mut by_awake: HashMap<u32, (u32, u32)> = HashMap::new();
+
+    for log in logs {
+        let lucid = log.dream.as_ref().is_some_and(|d| d.lucid == Some(true)) as u32;
+        match log.wbtb_alarm_used {
+            Some(_) => {
+                result.with_wbtb.0 += lucid;
+                result.with_wbtb.1 += 1;
+                if let Some(minutes) = log.awake_minutes(alarms) {
+                    let bucket = (minutes + AWAKE_BUCKET_MINUTES / 2) / AWAKE_BUCKET_MINUTES * AWAKE_BUCKET_MINUTES;
+                    let entry = by_awake.entry(bucket).or_default();
+                    entry.0 += lucid;
+                    entry.1 += 1;
+                }
+            }
+            None => {
+                result.without_wbtb.0 += lucid;
+                result.without_wbtb.1 += 1;
+            }
+        }
+    }
+
+    result.by_awake_minutes = by_awake.into_iter()
+        .map(|(minutes, (lucid, nights))| (minutes, lucid, nights))
+        .collect();
+    result.by_awake_minutes.sort_by_key(|(minutes, _, _)| *minutes);
+    result
+}
+
+/// Finds the technique key matching `query`, ignoring case and accepting
+/// unambiguous prefixes ("mil" → "MILD").
+pub fn resolve_technique<'a>(keys: impl IntoIterator<Item = &'a String>, query: &str) -> anyhow::Result<String> {
+    let query = query.trim();
+    let needle = query.to_lowercase();
+    let mut candidates = Vec::new();
+    for key in keys {
+        let lower = key.to_lowercase();
+        if lower == needle {
+            return Ok(key.clone());
+        }
+        if lower.starts_with(&needle) {
+            candidates.push(key.clone());
+        }
+    }
+    candidates.sort();
+
+    match candidates.len() {
+        0 => anyhow::bail!(JournalError::NotFound(format!("Technique not found: {}", query))),
+        1 => Ok(candidates.remove(0)),
+        _ => anyhow::bail!(JournalError::InvalidInput(format!("Ambiguous technique '{}': could be {}", query, candidates.join(", ")))),
+    }
+}
+
+pub fn default_techniques() -> HashMap<String, TechniqueData> {
+    let mut techniques = HashMap::new();
+
+    techniques.insert("MILD".to_string(), TechniqueData {
+        name: "Mnemonic Induction of Lucid Dreams (MILD)".to_string(),
+        description: "A technique that uses prospective memory to increase lucid dream frequency".to_string(),
+        steps: vec![
+            "Set intention to remember you're dreaming".to_string(),
+            "Visualize yourself becoming lucid in a recent dream".to_string(),
+            "Repeat a mantra like 'Next time I'm dreaming, I'll remember I'm dreaming'".to_string(),
+            "Fall asleep while maintaining this intention".to_string(),
+        ],
+        last_practiced: None,
+    });
+
+    techniques.insert("WBTB".to_string(), TechniqueData {
+        name: "Wake Back To Bed (WBTB)".to_string(),
+        description: "Wake up after 4-6 hours of sleep, stay awake briefly, then return to sleep".to_string(),
+        steps: vec![
+            "Set alarm for 4-6 hours after bedtime".to_string(),
+            "When alarm goes off, stay awake for 20-60 minutes".to_string(),
+            "Engage in lucid dream preparation activities".to_string(),
+            "Return to sleep while maintaining awareness".to_string(),
+        ],
+        last_practiced: None,
+    });
+
+    techniques.insert("FILD".to_string(), TechniqueData {
+        name: "Finger Induced Lucid Dream (FILD)".to_string(),
+        description: "A subtle finger movement technique to enter directly into a lucid dream".to_string(),
+        steps: vec![
+            "Wake up after 4-6 hours of sleep".to_string(),
+            "Lie completely still".to_string(),
+            "Gently move index and middle fingers as if playing piano".to_string(),
+            "After 10-20 seconds, perform a reality check".to_string(),
+        ],
+        last_practiced: None,
+    });
+
+    techniques.insert("RC".to_string(), TechniqueData {
+        name: "Reality Checks".to_string(),
+        description: "Habitual checks throughout the day to test if you're dreaming".to_string(),
+        steps: vec![
+            "Perform 10+ reality checks daily".to_string(),
+            "Question your reality: 'Am I dreaming?'".to_string(),
+            "Examine your environment for dream signs".to_string(),
+            "Try to push finger through palm or read text twice".to_string(),
+        ],
+        last_practiced: None,
+    });
+
+    techniques
+}
+
+/// Next local date-time at which `wake_time` (HH:MM) occurs, rolling over to tomorrow if it has passed.
+pub fn next_occurrence(wake_time: &str) -> anyhow::Result<DateTime<Local>> {
+    let now = Local::now();
+    let wake_naive = NaiveTime::parse_from_str(wake_time, "%H:%M")?;
+
+    let today = now.date_naive();
+    let at_wake = |date: NaiveDate| Local.from_local_datetime(&date.and_time(wake_naive)).earliest();
+
+    match at_wake(today) {
+        Some(wake_today) if wake_today > now => Ok(wake_today),
+        _ => {
+            let tomorrow = today.succ_opt().unwrap();
+            at_wake(tomorrow)
+                .ok_or_else(|| anyhow::anyhow!("{} does not exist in local time on {}", wake_time, tomorrow))
+        }
+    }
+}
+
+/// Up to `radius` characters either side of the first case-insensitive match of `keyword`.
+pub fn snippet(text: &str, keyword: &str, radius: usize) -> Option<String> {
+    let chars: Vec<char> = text.chars().collect();
+    let lower: Vec<char> = chars.iter().map(|c| c.to_lowercase().next().unwrap_or(*c)).collect();
+    let needle: Vec<char> = keyword.chars().map(|c| c.to_lowercase().next().unwrap_or(c)).collect();
+    if needle.is_empty() || needle.len() > lower.len() {
+        return None;
+    }
+    let at = lower.windows(needle.len()).position(|window| window == needle.as_slice())?;
+
+    let start = at.saturating_sub(radius);
+    let end = (at + needle.len() + radius).min(chars.len());
+    let excerpt: String = chars[start..end].iter().collect::<String>().replace('\n', " ");
+    let prefix = if start > 0 { "…" } else { "" };
+    let suffix = if end < chars.len() { "…" } else { "" };
+    Some(format!("{}{}{}", prefix, excerpt.trim(), suffix))
+}
+
+/// Byte ranges of the case-insensitive occurrences of `terms` in `text`, in order and never
+/// overlapping; where several terms match at one spot, the longest wins.
+pub fn find_terms(text: &str, terms: &[&str]) -> Vec<std::ops::Range<usize>> {
+    let lower = |c: char| c.to_lowercase().next().unwrap_or(c);
+    let mut terms: Vec<Vec<char>> = terms.iter()
+        .map(|term| term.chars().map(lower).collect::<Vec<char>>())
+        .filter(|term| !term.is_empty())
+        .collect();
+    terms.sort_by_key(|term| std::cmp::Reverse(term.len()));
+
+    let mut ranges = Vec::new();
+    let mut next_free = 0;
+    for (start, _) in text.char_indices() {
+        if start < next_free {
+            continue;
+        }
+        for term in &terms {
+            let mut chars = text[start..].char_indices();
+            let matched = term.iter().all(|t| chars.next().is_some_and(|(_, c)| lower(c) == *t));
+            if matched {
+                let end = chars.next().map_or(text.len(), |(offset, _)| start + offset);
+                ranges.push(start..end);
+                next_free = end;
+                break;
+            }
+        }
+    }
+    ranges
+}
+
+/// Daily logs mentioning `keyword` in their notes, wake feeling, tags or dream,
+/// each with `(field, snippet)` pairs for the matches.
+pub fn search_daily_logs<'a>(logs: &'a [DailyLog], keyword: &str) -> Vec<(&'a DailyLog, Vec<(&'static str, String)>)> {
+    logs.iter()
+        .filter_map(|log| {
+            let mut fields: Vec<(&'static str, &str)> = vec![("notes", &log.notes)];
+            if let Some(feeling) = &log.wake_feeling {
+                fields.push(("wake feeling", feeling));
+            }
+            fields.extend(log.sleep.iter().map(|sleep| ("sleep notes", sleep.notes.as_str())));
+            fields.extend(log.tags.iter().map(|tag| ("tag", tag.as_str())));
+            if let Some(dream) = &log.dream {
+                fields.push(("dream title", &dream.title));
+                fields.push(("dream", &dream.content));
+                fields.extend(dream.tags.iter().map(|tag| ("dream tag", tag.as_str())));
+            }
+
+            let hits: Vec<(&'static str, String)> = fields.into_iter()
+                .filter_map(|(field, text)| snippet(text, keyword, 30).map(|s| (field, s)))
+                .collect();
+            (!hits.is_empty()).then_some((log, hits))
+        })
+        .collect()
+}
+
+pub fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date, DATE_FORMAT).ok()
+}
+
+/// Days since the most recent dream recall, or `None` if no dream was ever recorded.
+pub fn dry_spell_days(dreams: &[Dream], logs: &[DailyLog], today: NaiveDate) -> Option<i64> {
+    dreams.iter()
+        .map(|d| d.date)
+        .chain(logs.iter().filter(|log| log.dream.is_some()).map(|log| log.date))
+        .filter(|date| *date <= today)
+        .max()
+        .map(|last| (today - last).num_days())
+}
+
+/// Each technique with the days since it was last practiced (`None` if never), most overdue first.
+/// Uses the later of `last_practiced` and the newest session in `history`.
+pub fn technique_rotation(techniques: &HashMap<String, TechniqueData>, history: &[TechniquePractice], today: NaiveDate) -> Vec<(String, Option<i64>)> {
+    let mut rotation: Vec<(String, Option<i64>)> = techniques.iter()
+        .map(|(key, data)| {
+            let days = history.iter()
+                .filter(|practice| practice.technique == *key)
+                .map(|practice| practice.date)
+                .chain(data.last_practiced)
+                .max()
+                .map(|date| (today - date).num_days());
+            (key.clone(), days)
+        })
+        .collect();
+    rotation.sort_by(|a, b| {
+        let overdue = |days: Option<i64>| days.unwrap_or(i64::MAX);
+        overdue(b.1).cmp(&overdue(a.1)).then_with(|| a.0.cmp(&b.0))
+    });
+    rotation
+}
+
+/// Whether it's past `hour` on `now`'s local day and no daily log covers it yet.
+/// Logs are dated in UTC, so a log for either the local or the UTC date counts.
+pub fn daily_log_missing(logs: &[DailyLog], now: DateTime<Local>, hour: u32) -> bool {
+    if now.hour() < hour {
+        return false;
+    }
+    let local = now.date_naive();
+    let utc = now.with_timezone(&Utc).date_naive();
+    !logs.iter().any(|log| log.date == local || log.date == utc)
+}
+
+/// Splits text into lowercase words, grouped into runs that never cross punctuation.
+pub fn tokenize_segments(text: &str) -> Vec<Vec<String>> {
+    let mut segments = Vec::new();
+    let mut current = Vec::new();
+
+    for chunk in text.split_whitespace() {
+        let word = chunk.trim_matches(|c: char| !c.is_alphanumeric()).to_lowercase();
+        let breaks_before = chunk.starts_with(|c: char| !c.is_alphanumeric());
+        let breaks_after = chunk.ends_with(|c: char| !c.is_alphanumeric());
+
+        if (breaks_before || word.is_empty()) && !current.is_empty() {
+            segments.push(std::mem::take(&mut current));
+        }
+        if !word.is_empty() {
+            current.push(word);
+        }
+        if breaks_after && !current.is_empty() {
+            segments.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        segments.push(current);
+    }
+
+    segments
+}
+
+pub fn tokenize(text: &str) -> Vec<String> {
+    tokenize_segments(text).into_iter().flatten().collect()
+}
+
+/// How many words `text` has, counted the way `tokenize` splits them.
+pub fn word_count(text: &str) -> usize {
+    tokenize_segments(text).iter().map(Vec::len).sum()
+}
+
+/// Tags from `rules` whose keyword patterns match a word of `text`, sorted and `#`-prefixed.
+pub fn suggest_tags(rules: &HashMap<String, String>, text: &str) -> Vec<String> {
+    let words: HashSet<String> = tokenize(text).into_iter().collect();
+    let matches = |pattern: &str| pattern.split('|')
+        .map(|p| p.trim().to_lowercase())
+        .filter(|p| !p.is_empty())
+        .any(|p| match p.strip_suffix('*') {
+            Some(prefix) => words.iter().any(|w| w.starts_with(prefix)),
+            None => words.contains(&p),
+        });
+
+    let mut tags: Vec<String> = rules.iter()
+        .filter(|(pattern, _)| matches(pattern))
+        .map(|(_, tag)| format!("#{}", tag.trim().trim_start_matches('#')))
+        .collect();
+    tags.sort();
+    tags.dedup();
+    tags
+}
+
+/// Editor buffer for a dream template: each section heading on its own line, with room to write below.
+pub fn template_buffer(sections: &[String]) -> String {
+    sections.iter().map(|section| format!("{}:\n\n", section)).collect()
+}
+
+/// Dream content from `(section, text)` answers, one `Section: text` paragraph each. Sections left
+/// empty are dropped, and text under an empty section name is kept as is.
+pub fn fill_template(answers: &[(String, String)]) -> String {
+    answers.iter()
+        .filter(|(_, text)| !text.trim().is_empty())
+        .map(|(section, text)| match section.as_str() {
+            "" => text.trim().to_string(),
+            section => format!("{}: {}", section, text.trim()),
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Splits an edited `template_buffer` back into `(section, text)` answers for `fill_template`. A line
+/// starting with one of the `sections` followed by a colon opens that section; anything written
+/// before the first heading goes under an empty section name.
+pub fn parse_template(text: &str, sections: &[String]) -> Vec<(String, String)> {
+    let mut answers = vec![(String::new(), String::new())];
+    for line in text.lines() {
+        let heading = sections.iter().find_map(|section| {
+            let (head, rest) = line.trim_start().split_at_checked(section.len())?;
+            let rest = rest.strip_prefix(':')?;
+            head.eq_ignore_ascii_case(section).then_some((section, rest))
+        });
+        match heading {
+            Some((section, rest)) => answers.push((section.clone(), rest.trim().to_string())),
+            None => {
+                let text = &mut answers.last_mut().unwrap().1;
+                text.push('\n');
+                text.push_str(line);
+            }
+        }
+    }
+    answers
+}
+
+/// Share of distinct words two texts have in common (Jaccard index), from 0.0 to 1.0.
+pub fn word_overlap(a: &str, b: &str) -> f32 {
+    let a: HashSet<String> = tokenize(a).into_iter().collect();
+    let b: HashSet<String> = tokenize(b).into_iter().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f32 / union as f32
+}
+
+/// Cosine similarity of the non-stop-word counts of two texts, from 0.0 to 1.0.
+pub fn cosine_similarity(a: &str, b: &str) -> f32 {
+    let counts = |text: &str| {
+        let mut counts: HashMap<String, f32> = HashMap::new();
+        for word in tokenize(text).into_iter().filter(|w| !is_stop_word(w)) {
+            *counts.entry(word).or_insert(0.0) += 1.0;
+        }
+        counts
+    };
+    let (a, b) = (counts(a), counts(b));
+    let norm = |counts: &HashMap<String, f32>| counts.values().map(|c| c * c).sum::<f32>().sqrt();
+    let denominator = norm(&a) * norm(&b);
+    if denominator == 0.0 {
+        return 0.0;
+    }
+    a.iter().filter_map(|(word, count)| b.get(word).map(|other| count * other)).sum::<f32>() / denominator
+}
+
+/// How much a shared tag adds to a related-dream score, on top of the 0-1 text similarity.
+const RELATED_TAG_WEIGHT: f32 = 0.25;
+
+/// A dream similar to another one, see [`related_dreams`].
+#[derive(Debug, Clone)]
+pub struct RelatedDream<'a> {
+    pub dream: &'a Dream,
+    pub shared_tags: usize,
+    pub similarity: f32,
+    pub score: f32,
+}
+
+/// Up to `limit` dreams most like `target` by shared tags and word-count similarity, best first.
+pub fn related_dreams<'a>(target: &Dream, dreams: &'a [Dream], limit: usize) -> Vec<RelatedDream<'a>> {
+    let text = |dream: &Dream| format!("{} {}", dream.title, dream.content);
+    let target_text = text(target);
+
+    let mut related: Vec<RelatedDream> = dreams.iter()
+        .filter(|d| d.id != target.id)
+        .map(|dream| {
+            let shared_tags = dream.tags.iter().filter(|t| target.tags.contains(t)).count();
+            let similarity = cosine_similarity(&target_text, &text(dream));
+            RelatedDream { dream, shared_tags, similarity, score: similarity + RELATED_TAG_WEIGHT * shared_tags as f32 }
+        })
+        .filter(|r| r.score > 0.0)
+        .collect();
+    related.sort_by(|a, b| b.score.total_cmp(&a.score).then(a.dream.id.cmp(&b.dream.id)));
+    related.truncate(limit);
+    related
+}
+
+/// An existing dream from `date` that looks like the same entry: an equal title once
+/// case and punctuation are ignored, or mostly the same words in the content.
+pub fn find_duplicate<'a>(dreams: &'a [Dream], date: NaiveDate, title: &str, content: &str) -> Option<&'a Dream> {
+    const OVERLAP_THRESHOLD: f32 = 0.6;
+    let normalized = tokenize(title);
+    dreams.iter()
+        .filter(|d| d.date == date)
+        .find(|d| tokenize(&d.title) == normalized || word_overlap(&d.content, content) >= OVERLAP_THRESHOLD)
+}
+
+/// Only the most frequent signs are paired up, which keeps the pair count bounded for big journals.
+const MAX_CLUSTER_SIGNS: usize = 40;
+
+/// Two dream signs (tags or recorded dream signs) that showed up in the same dreams.
+#[derive(Debug, Clone)]
+pub struct SignPair {
+    pub first: String,
+    pub second: String,
+    pub together: u32,
+    pub lucid: u32,
+}
+
+impl SignPair {
+    pub fn lucid_rate(&self) -> f32 {
+        self.lucid as f32 / self.together.max(1) as f32 * 100.0
+    }
+}
+
+/// Lowercase tags and dream sign of a dream, without `#` and the `lucid` tag itself.
+fn dream_signs(dream: &Dream) -> Vec<String> {
+    let mut signs: Vec<String> = dream.tags.iter()
+        .map(String::as_str)
+        .chain(dream.dream_sign.as_deref())
+        .map(|sign| sign.trim().trim_start_matches('#').to_lowercase())
+        .filter(|sign| !sign.is_empty() && sign != "lucid")
+        .collect();
+    signs.sort();
+    signs.dedup();
+    signs
+}
+
+/// Pairs of signs seen together in at least two dreams, most frequent first.
+pub fn sign_pairs(dreams: &[Dream]) -> Vec<SignPair> {
+    let mut frequency: HashMap<String, u32> = HashMap::new();
+    for dream in dreams {
+        for sign in dream_signs(dream) {
+            *frequency.entry(sign).or_insert(0) += 1;
+        }
+    }
+    let mut common: Vec<(String, u32)> = frequency.into_iter().filter(|(_, count)| *count > 1).collect();
+    common.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let common: HashSet<String> = common.into_iter().take(MAX_CLUSTER_SIGNS).map(|(sign, _)| sign).collect();
+
+    let mut pairs: HashMap<(String, String), (u32, u32)> = HashMap::new();
+    for dream in dreams {
+        let signs: Vec<String> = dream_signs(dream).into_iter().filter(|s| common.contains(s)).collect();
+        for (i, first) in signs.iter().enumerate() {
+            for second in &signs[i + 1..] {
+                let entry = pairs.entry((first.clone(), second.clone())).or_insert((0, 0));
+                entry.0 += 1;
+                if dream.lucid == Some(true) {
+                    entry.1 += 1;
+                }
+            }
+        }
+    }
+
+    let mut pairs: Vec<SignPair> = pairs.into_iter()
+        .filter(|(_, (together, _))| *together > 1)
+        .map(|((first, second), (together, lucid))| SignPair { first, second, together, lucid })
+        .collect();
+    pairs.sort_by(|a, b| b.together.cmp(&a.together)
+        .then_with(|| a.first.cmp(&b.first))
+        .then_with(|| a.second.cmp(&b.second)));
+    pairs
+}
+
+/// Most frequent `n`-word phrases across `texts`, highest count first, ties broken alphabetically.
+pub fn top_ngrams<'a>(texts: impl Iterator<Item = &'a str>, n: usize, limit: usize) -> Vec<(String, u32)> {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    for text in texts {
+        for segment in tokenize_segments(text) {
+            for window in segment.windows(n) {
+                *counts.entry(window.join(" ")).or_insert(0) += 1;
+            }
+        }
+    }
+
+    let mut sorted: Vec<(String, u32)> = counts.into_iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    sorted.truncate(limit);
+    sorted
+}
+
+const STOP_WORDS: &[&str] = &[
+    "a", "about", "after", "again", "all", "am", "an", "and", "any", "are", "as", "at", "be", "been",
+    "before", "but", "by", "can", "could", "did", "do", "for", "from", "had", "has", "have", "he",
+    "her", "him", "his", "how", "i", "if", "in", "into", "is", "it", "its", "just", "me", "my", "not",
+    "of", "on", "or", "our", "out", "she", "so", "some", "that", "the", "their", "them", "then",
+    "there", "they", "this", "to", "up", "us", "was", "we", "were", "what", "when", "where", "which",
+    "while", "who", "with", "would", "you", "your",
+];
+
+pub fn is_stop_word(word: &str) -> bool {
+    STOP_WORDS.contains(&word)
+}
+
+/// Occurrences of each non-stop word in `texts`, and their total.
+fn content_word_counts<'a>(texts: impl Iterator<Item = &'a str>) -> (HashMap<String, u32>, u32) {
+    let mut counts: HashMap<String, u32> = HashMap::new();
+    let mut total = 0;
+    for text in texts {
+        for word in tokenize(text) {
+            if !is_stop_word(&word) {
+                *counts.entry(word).or_insert(0) += 1;
+                total += 1;
+            }
+        }
+    }
+    (counts, total)
+}
+
+/// Times a word must appear across both windows before `term_trends` reports it.
+pub const MIN_TREND_COUNT: u32 = 3;
+
+/// How often a word came up in recent and in earlier dreams, per 1000 content words of each.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TermTrend {
+    pub word: String,
+    pub recent: f32,
+    pub earlier: f32,
+}
+
+impl TermTrend {
+    pub fn change(&self) -> f32 {
+        self.recent - self.earlier
+    }
+}
+
+/// The words whose share of dream content rose most, and fell most, from `earlier` to `recent`
+/// texts, biggest change first and at most `limit` of each. Empty when either window has no words.
+pub fn term_trends<'a>(recent: impl Iterator<Item = &'a str>, earlier: impl Iterator<Item = &'a str>, limit: usize) -> (Vec<TermTrend>, Vec<TermTrend>) {
+    let (recent, recent_total) = content_word_counts(recent);
+    let (earlier, earlier_total) = content_word_counts(earlier);
+    if recent_total == 0 || earlier_total == 0 {
+        return (Vec::new(), Vec::new());
+    }
+
+    let per_thousand = |count: u32, total: u32| count as f32 * 1000.0 / total as f32;
+    let words: HashSet<&String> = recent.keys().chain(earlier.keys()).collect();
+    let mut trends: Vec<TermTrend> = words.into_iter()
+        .filter_map(|word| {
+            let (now, before) = (recent.get(word).copied().unwrap_or(0), earlier.get(word).copied().unwrap_or(0));
+            (now + before >= MIN_TREND_COUNT).then(|| TermTrend {
+                word: word.clone(),
+                recent: per_thousand(now, recent_total),
+                earlier: per_thousand(before, earlier_total),
+            })
+        })
+        .collect();
+    trends.sort_by(|a, b| b.change().total_cmp(&a.change()).then_with(|| a.word.cmp(&b.word)));
+
+    let rising = trends.iter().filter(|t| t.change() > 0.0).take(limit).cloned().collect();
+    let mut falling: Vec<TermTrend> = trends.into_iter().filter(|t| t.change() < 0.0).collect();
+    falling.sort_by(|a, b| a.change().total_cmp(&b.change()).then_with(|| a.word.cmp(&b.word)));
+    falling.truncate(limit);
+    (rising, falling)
+}
+
+/// Most frequent non-stop-words across `texts`, highest count first, ties broken alphabetically.
+pub fn top_words<'a>(texts: impl Iterator<Item = &'a str>, limit: usize) -> Vec<(String, u32)> {
+    let (counts, _) = content_word_counts(texts);
+    let mut sorted: Vec<(String, u32)> = counts.into_iter().collect();
+    sorted.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    sorted.truncate(limit);
+    sorted
+}
+
+/// Lucid-dream counts worth celebrating, besides the first one.
+pub const LUCID_MILESTONES: [usize; 7] = [10, 25, 50, 100, 250, 500, 1000];
+
+/// A lucid dream that reached a milestone: the first one, or one of `LUCID_MILESTONES`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LucidMilestone {
+    /// Lucid dreams in the journal, this one included.
+    pub count: usize,
+    /// Days from the first journal entry to this dream.
+    pub days_since_start: i64,
+}
+
+impl LucidMilestone {
+    pub fn first(&self) -> bool {
+        self.count == 1
+    }
+}
+
+/// The milestone `dream`, just marked lucid, reached among `dreams` (which include it), if any.
+pub fn lucid_milestone(dreams: &[Dream], dream: &Dream) -> Option<LucidMilestone> {
+    let count = dreams.iter().filter(|d| d.lucid == Some(true)).count();
+    if count != 1 && !LUCID_MILESTONES.contains(&count) {
+        return None;
+    }
+    let start = dreams.iter().map(|d| d.date).min()?;
+    Some(LucidMilestone { count, days_since_start: (dream.date - start).num_days() })
+}
+
+/// Longest run of consecutive calendar days among `dates`.
+pub fn longest_streak(dates: impl Iterator<Item = NaiveDate>) -> u32 {
+    let mut days: Vec<NaiveDate> = dates.collect();
+    days.sort();
+    days.dedup();
+
+    let mut longest = 0;
+    let mut current = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for day in days {
+        current = match previous {
+            Some(prev) if day - prev == chrono::Duration::days(1) => current + 1,
+            _ => 1,
+        };
+        longest = longest.max(current);
+        previous = Some(day);
+    }
+    longest
+}
+
+/// Formats one CSV line, quoting fields that contain commas, quotes or newlines.
+pub fn csv_row<S: AsRef<str>>(fields: &[S]) -> String {
+    let escaped: Vec<String> = fields.iter()
+        .map(|field| {
+            let field = field.as_ref();
+            if field.contains([',', '"', '\n', '\r']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.to_string()
+            }
+        })
+        .collect();
+    escaped.join(",") + "\n"
+}
+
+/// Techniques from most to least effective by success rate, ties broken by name.
+pub fn rank_techniques(stats: &HashMap<String, TechniqueStats>) -> Vec<(&String, &TechniqueStats)> {
+    let mut ranked: Vec<_> = stats.iter().collect();
+    ranked.sort_by(|a, b| b.1.success_rate.total_cmp(&a.1.success_rate).then_with(|| a.0.cmp(b.0)));
+    ranked
+}
+
+/// One row per technique, most effective first, with its rank in the comparison.
+pub fn effectiveness_csv(stats: &HashMap<String, TechniqueStats>) -> String {
+    let mut csv = csv_row(&[
+        "rank", "technique", "attempts", "successes", "success_rate", "weighted_success_rate", "last_practiced",
+        "total_minutes", "average_minutes",
+    ]);
+    for (rank, (technique, data)) in rank_techniques(stats).into_iter().enumerate() {
+        csv.push_str(&csv_row(&[
+            (rank + 1).to_string(),
+            technique.clone(),
+            data.attempts.to_string(),
+            data.successes.to_string(),
+            format!("{:.1}", data.success_rate),
+            format!("{:.1}", data.weighted_success_rate),
+            data.last_practiced.format(DATE_FORMAT).to_string(),
+            data.total_minutes.to_string(),
+            format!("{:.1}", data.average_minutes),
+        ]));
+    }
+    csv
+}
+
+pub fn technique_history_csv(history: &[TechniquePractice]) -> String {
+    let mut sorted: Vec<&TechniquePractice> = history.iter().collect();
+    sorted.sort_by_key(|d| d.date);
+
+    let mut csv = csv_row(&["date", "technique", "duration_minutes", "outcome", "control_level"]);
+    for practice in sorted {
+        let control_level = match practice.outcome {
+            TechniqueOutcome::FullLucid { control_level } => control_level.to_string(),
+            _ => String::new(),
+        };
+        csv.push_str(&csv_row(&[
+            practice.date.format(DATE_FORMAT).to_string(),
+            practice.technique.clone(),
+            practice.duration_minutes.to_string(),
+            practice.outcome.label().to_string(),
+            control_level,
+        ]));
+    }
+    csv
+}
+
+const HTML_STYLE: &str = "body { font-family: Georgia, serif; max-width: 46em; margin: 2em auto; padding: 0 1em; color: #222; background: #fbfaf7; line-height: 1.5; }
+h1, h2, h3 { font-family: Helvetica, Arial, sans-serif; }
+h2 { border-bottom: 1px solid #ccc; padding-bottom: 0.2em; margin-top: 2em; }
+nav a { margin-right: 0.6em; }
+table { border-collapse: collapse; }
+td, th { padding: 0.2em 0.8em; text-align: left; border-bottom: 1px solid #e4e4e4; }
+.dream { margin: 1em 0; padding: 0.6em 1em; border-left: 4px solid #ccc; background: #fff; }
+.dream.lucid { border-left-color: #7b4fd6; background: #f4effd; }
+.meta { color: #666; font-size: 0.9em; }
+.badge { color: #7b4fd6; font-weight: bold; }";
+
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// The whole journal as one self-contained HTML page: a summary with technique success rates, then
+/// every dream grouped by month, newest first, with lucid dreams highlighted.
+pub fn journal_html(dreams: &[Dream], techniques: &HashMap<String, TechniqueStats>, generated: NaiveDate) -> String {
+    use std::fmt::Write as _;
+
+    let mut sorted: Vec<&Dream> = dreams.iter().collect();
+    sorted.sort_by(|a, b| b.date.cmp(&a.date).then(b.id.cmp(&a.id)));
+    let mut months: Vec<(String, Vec<&Dream>)> = Vec::new();
+    for dream in sorted {
+        let month = dream.date.format("%Y-%m").to_string();
+        match months.last_mut() {
+            Some((last, dreams)) if *last == month => dreams.push(dream),
+            _ => months.push((month, vec![dream])),
+        }
+    }
+
+    let lucid = dreams.iter().filter(|d| d.lucid == Some(true)).count();
+    let mut html = String::new();
+    let _ = write!(html, "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n\
+        <title>Dream Journal</title>\n<style>\n{}\n</style>\n</head>\n<body>\n<h1>Dream Journal</h1>\n\
+        <p class=\"meta\">Exported {}</p>\n", HTML_STYLE, generated.format(DATE_FORMAT));
+
+    html.push_str("<h2>Summary</h2>\n<table>\n");
+    let _ = writeln!(html, "<tr><th>Dreams</th><td>{}</td></tr>", dreams.len());
+    let _ = writeln!(html, "<tr><th>Lucid dreams</th><td>{} ({:.1}%)</td></tr>", lucid,
+        if dreams.is_empty() { 0.0 } else { lucid as f32 * 100.0 / dreams.len() as f32 });
+    let _ = writeln!(html, "<tr><th>Longest recall streak</th><td>{} days</td></tr>", longest_streak(dreams.iter().map(|d| d.date)));
+    let words: Vec<String> = top_words(dreams.iter().map(|d| d.content.as_str()), 10).into_iter()
+        .map(|(word, count)| format!("{} ({})", html_escape(&word), count))
+        .collect();
+    if !words.is_empty() {
+        let _ = writeln!(html, "<tr><th>Common words</th><td>{}</td></tr>", words.join(", "));
+    }
+    html.push_str("</table>\n");
+
+    if !techniques.is_empty() {
+        html.push_str("<h3>Techniques</h3>\n<table>\n<tr><th>Technique</th><th>Attempts</th><th>Successes</th><th>Success rate</th></tr>\n");
+        for (name, stats) in rank_techniques(techniques) {
+            let _ = writeln!(html, "<tr><td>{}</td><td>{}</td><td>{}</td><td>{:.1}%</td></tr>",
+                html_escape(name), stats.attempts, stats.successes, stats.success_rate);
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !months.is_empty() {
+        html.push_str("<nav>\n");
+        for (month, _) in &months {
+            let _ = writeln!(html, "<a href=\"#m{}\">{}</a>", month, month);
+        }
+        html.push_str("</nav>\n");
+    }
+    for (month, dreams) in &months {
+        let _ = writeln!(html, "<h2 id=\"m{}\">{} <span class=\"meta\">({} dreams)</span></h2>", month, month, dreams.len());
+        for dream in dreams {
+            let lucid = dream.lucid == Some(true);
+            let _ = writeln!(html, "<article class=\"dream{}\">", if lucid { " lucid" } else { "" });
+            let _ = writeln!(html, "<h3>{}{}{}</h3>", if dream.starred { "★ " } else { "" }, html_escape(&dream.title),
+                if lucid { " <span class=\"badge\">lucid</span>" } else { "" });
+            let mut meta = vec![format!("#{}", dream.id), dream.date.format(DATE_FORMAT).to_string()];
+            if !dream.tags.is_empty() {
+                meta.push(html_escape(&dream.tags.join(", ")));
+            }
+            if let Some(sign) = dream.dream_sign.as_deref().filter(|s| !s.is_empty()) {
+                meta.push(format!("dream sign: {}", html_escape(sign)));
+            }
+            if let Some(setting) = dream.setting.as_deref() {
+                meta.push(format!("setting: {}", html_escape(setting)));
+            }
+            if let Some(vividness) = dream.vividness {
+                meta.push(format!("vividness {}/{}", vividness, VIVIDNESS_SCALE));
+            }
+            let _ = writeln!(html, "<p class=\"meta\">{}</p>", meta.join(" · "));
+            for paragraph in dream.content.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+                let _ = writeln!(html, "<p>{}</p>", html_escape(paragraph).replace('\n', "<br>\n"));
+            }
+            html.push_str("</article>\n");
+        }
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// The "HH:MM" that is `minutes` after `bedtime`, wrapping past midnight.
+pub fn time_after(bedtime: &str, minutes: u32) -> anyhow::Result<String> {
+    let bedtime = NaiveTime::parse_from_str(bedtime, "%H:%M")
+        .map_err(|_| JournalError::InvalidInput(format!("Invalid bedtime '{}', expected HH:MM", bedtime)))?;
+    Ok((bedtime + chrono::Duration::minutes(minutes as i64)).format("%H:%M").to_string())
+}
+
+pub fn next_trigger(alarm: &WBTBAlarm) -> anyhow::Result<DateTime<Local>> {
+    next_occurrence(&alarm.wake_time)
+}
+
+/// Minutes slept between bedtime and wake time, wrapping past midnight.
+pub fn sleep_minutes(sleep: &SleepLog) -> Option<i64> {
+    span_minutes(&sleep.bedtime, &sleep.wake_time)
+}
+
+/// Minutes from `bedtime` to `wake_time` (both HH:MM), wrapping past midnight.
+pub fn span_minutes(bedtime: &str, wake_time: &str) -> Option<i64> {
+    let bedtime = NaiveTime::parse_from_str(bedtime, "%H:%M").ok()?;
+    let wake_time = NaiveTime::parse_from_str(wake_time, "%H:%M").ok()?;
+    let mut minutes = (wake_time - bedtime).num_minutes();
+    if minutes < 0 {
+        minutes += 24 * 60;
+    }
+    Some(minutes)
+}
+
+/// Hours slept short of `target_hours` over the `days` nights ending `today`; extra sleep pays
+/// the debt back, but never below zero.
+pub fn sleep_debt_hours<'a>(
+    sleeps: impl Iterator<Item = &'a SleepLog>,
+    target_hours: f32,
+    today: NaiveDate,
+    days: i64,
+) -> f32 {
+    // Naps count towards the day they were taken, not as short nights of their own.
+    let mut per_day: HashMap<NaiveDate, i64> = HashMap::new();
+    for sleep in sleeps.filter(|s| (0..days).contains(&(today - s.date).num_days())) {
+        if let Some(minutes) = sleep_minutes(sleep) {
+            *per_day.entry(sleep.date).or_insert(0) += minutes;
+        }
+    }
+    let debt: f32 = per_day.values()
+        .map(|minutes| target_hours - *minutes as f32 / 60.0)
+        .sum();
+    debt.max(0.0)
+}
+
+/// The REM-heavy back third of a night's sleep, where lucid dreams are most likely.
+pub fn rem_window(sleep: &SleepLog) -> Option<(NaiveTime, NaiveTime)> {
+    let bedtime = NaiveTime::parse_from_str(&sleep.bedtime, "%H:%M").ok()?;
+    let minutes = sleep_minutes(sleep)?;
+    let start = bedtime + chrono::Duration::minutes(minutes * 2 / 3);
+    let end = bedtime + chrono::Duration::minutes(minutes);
+    Some((start, end))
+}
+
+/// Typical length of one sleep cycle, light and deep sleep followed by REM.
+pub const SLEEP_CYCLE_MINUTES: i64 = 90;
+
+/// One estimated sleep cycle; the last one is cut short if the night ends mid-cycle.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SleepCycle {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+    /// Where the REM period at the end of the cycle begins, if the cycle reaches it.
+    pub rem_start: Option<NaiveTime>,
+    /// REM in the back third of the night, where lucidity is most achievable.
+    pub lucid_window: bool,
+}
+
+impl SleepCycle {
+    pub fn minutes(&self) -> i64 {
+        (self.end - self.start).num_minutes().rem_euclid(24 * 60)
+    }
+}
+
+/// Estimated cycles of `cycle_minutes` from `bedtime` to `wake_time` (HH:MM). REM closes each
+/// cycle and lengthens through the night, from about 10 minutes to half of a cycle.
+pub fn sleep_cycles(bedtime: &str, wake_time: &str, cycle_minutes: i64) -> Option<Vec<SleepCycle>> {
+    let night = span_minutes(bedtime, wake_time)?;
+    let bedtime = NaiveTime::parse_from_str(bedtime, "%H:%M").ok()?;
+    if cycle_minutes <= 0 {
+        return None;
+    }
+    let minutes = |m: i64| chrono::Duration::minutes(m);
+
+    let mut cycles = Vec::new();
+    let mut offset = 0;
+    while offset < night {
+        let length = cycle_minutes.min(night - offset);
+        let rem = (10 * (cycles.len() as i64 + 1)).min(cycle_minutes / 2);
+        let rem_offset = cycle_minutes - rem;
+        let rem_start = (length > rem_offset).then(|| bedtime + minutes(offset + rem_offset));
+        cycles.push(SleepCycle {
+            start: bedtime + minutes(offset),
+            end: bedtime + minutes(offset + length),
+            rem_start,
+            lucid_window: rem_start.is_some() && offset + length > night * 2 / 3,
+        });
+        offset += length;
+    }
+    Some(cycles)
+}
+
+/// Average bedtime and sleep length in minutes. Bedtimes are averaged from noon so late nights don't wrap.
+pub fn typical_schedule<'a>(sleeps: impl Iterator<Item = &'a SleepLog>) -> Option<(NaiveTime, i64)> {
+    let noon = NaiveTime::from_hms_opt(12, 0, 0).unwrap();
+    let mut count = 0;
+    let mut bedtime_total = 0;
+    let mut duration_total = 0;
+
+    for sleep in sleeps {
+        if let (Ok(bedtime), Some(minutes)) = (NaiveTime::parse_from_str(&sleep.bedtime, "%H:%M"), sleep_minutes(sleep)) {
+            let mut from_noon = (bedtime - noon).num_minutes();
+            if from_noon < 0 {
+                from_noon += 24 * 60;
+            }
+            bedtime_total += from_noon;
+            duration_total += minutes;
+            count += 1;
+        }
+    }
+
+    if count == 0 {
+        return None;
+    }
+
+    let bedtime = noon + chrono::Duration::minutes(bedtime_total / count);
+    Some((bedtime, duration_total / count))
+}
+
+/// Minutes since midnight for an `HH:MM` time.
+fn minutes_of_day(time: &str) -> Option<i64> {
+    NaiveTime::parse_from_str(time, "%H:%M").ok().map(|t| i64::from(t.num_seconds_from_midnight() / 60))
+}
+
+/// Standard deviation in minutes of clock times, treating the day as a circle so that
+/// 23:50 and 00:10 are 20 minutes apart rather than 23 hours 40 minutes.
+fn clock_spread(minutes: &[i64]) -> f32 {
+    let day = (24 * 60) as f32;
+    let angles: Vec<f32> = minutes.iter().map(|&m| m as f32 / day * std::f32::consts::TAU).collect();
+    let mean = angles.iter().map(|a| a.sin()).sum::<f32>().atan2(angles.iter().map(|a| a.cos()).sum::<f32>());
+    let mean_minutes = mean / std::f32::consts::TAU * day;
+
+    let variance = minutes.iter()
+        .map(|&m| {
+            let diff = (m as f32 - mean_minutes).rem_euclid(day);
+            let diff = if diff > day / 2.0 { diff - day } else { diff };
+            diff * diff
+        })
+        .sum::<f32>() / minutes.len() as f32;
+    variance.sqrt()
+}
+
+/// How consistent bedtimes and wake times are over a run of nights.
+#[derive(Debug, Clone, Copy)]
+pub struct SleepRegularity {
+    pub nights: usize,
+    pub bedtime_spread_minutes: f32,
+    pub wake_spread_minutes: f32,
+    /// 100 for the same times every night, down to 0 once times vary by two hours or more.
+    pub score: u32,
+}
+
+impl SleepRegularity {
+    /// Whether bedtimes or wake times swing by more than an hour.
+    pub fn drifting(&self) -> bool {
+        self.bedtime_spread_minutes > 60.0 || self.wake_spread_minutes > 60.0
+    }
+}
+
+/// Regularity of the given nights; needs at least three with readable times.
+pub fn sleep_regularity<'a>(sleeps: impl Iterator<Item = &'a SleepLog>) -> Option<SleepRegularity> {
+    let (bedtimes, wake_times): (Vec<i64>, Vec<i64>) = sleeps
+        .filter_map(|sleep| minutes_of_day(&sleep.bedtime).zip(minutes_of_day(&sleep.wake_time)))
+        .unzip();
+    if bedtimes.len() < 3 {
+        return None;
+    }
+
+    let bedtime_spread_minutes = clock_spread(&bedtimes);
+    let wake_spread_minutes = clock_spread(&wake_times);
+    let average = (bedtime_spread_minutes + wake_spread_minutes) / 2.0;
+    Some(SleepRegularity {
+        nights: bedtimes.len(),
+        bedtime_spread_minutes,
+        wake_spread_minutes,
+        score: (100.0 - average / 120.0 * 100.0).clamp(0.0, 100.0).round() as u32,
+    })
+}
+
+/// Suggested WBTB wake time: the start of the REM-heavy window of a typical night, rounded to 15 minutes.
+pub fn suggested_wbtb_time(bedtime: NaiveTime, sleep_minutes: i64) -> NaiveTime {
+    let wake = bedtime + chrono::Duration::minutes(sleep_minutes * 2 / 3);
+    let minutes = ((wake.num_seconds_from_midnight() / 60 + 7) / 15 * 15) % (24 * 60);
+    NaiveTime::from_hms_opt(minutes / 60, minutes % 60, 0).unwrap()
+}
+
+/// Groups `(date, value)` pairs into `TREND_DAYS` daily buckets, oldest first, ending at `today`.
+pub fn bucket_by_day(
+    entries: impl Iterator<Item = (NaiveDate, f32)>,
+    today: NaiveDate,
+) -> Vec<Vec<f32>> {
+    let mut buckets = vec![Vec::new(); TREND_DAYS];
+    for (date, value) in entries {
+        let days_ago = (today - date).num_days();
+        if (0..TREND_DAYS as i64).contains(&days_ago) {
+            buckets[TREND_DAYS - 1 - days_ago as usize].push(value);
+        }
+    }
+    buckets
+}
+
+pub fn sparkline(values: &[Option<f32>], min: f32, max: f32) -> String {
+    values.iter()
+        .map(|value| match value {
+            Some(v) if max > min => {
+                let level = ((v - min) / (max - min) * (SPARK_BLOCKS.len() - 1) as f32).round();
+                SPARK_BLOCKS[level.clamp(0.0, (SPARK_BLOCKS.len() - 1) as f32) as usize]
+            }
+            Some(_) => SPARK_BLOCKS[0],
+            None => ' ',
+        })
+        .collect()
+}
+
+/// Middle value of `values`, averaging the two middle ones for an even count.
+pub fn median(values: &[f32]) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let mid = sorted.len() / 2;
+    Some(if sorted.len().is_multiple_of(2) { (sorted[mid - 1] + sorted[mid]) / 2.0 } else { sorted[mid] })
+}
+
+/// The `p`th percentile (0-100) of `values`, interpolating between the two nearest ranks.
+pub fn percentile(values: &[f32], p: f32) -> Option<f32> {
+    if values.is_empty() {
+        return None;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_by(f32::total_cmp);
+    let rank = p.clamp(0.0, 100.0) / 100.0 * (sorted.len() - 1) as f32;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f32))
+}
+
+/// Counts `values` into bins of `bin_width` starting at the bin holding the minimum.
+/// The width is widened to a multiple of `bin_width` when more than `max_bins` would be needed.
+/// Returns each bin's lower bound, its width and its count.
+pub fn histogram(values: &[f32], bin_width: f32, max_bins: usize) -> Vec<(f32, f32, usize)> {
+    let (Some(min), Some(max)) = (
+        values.iter().copied().reduce(f32::min),
+        values.iter().copied().reduce(f32::max),
+    ) else {
+        return Vec::new();
+    };
+
+    let start = (min / bin_width).floor() * bin_width;
+    let needed = ((max - start) / bin_width).floor() as usize + 1;
+    let width = bin_width * needed.div_ceil(max_bins.max(1)) as f32;
+    let bins = ((max - start) / width).floor() as usize + 1;
+
+    let mut counts = vec![0; bins];
+    for value in values {
+        let index = ((value - start) / width).floor() as usize;
+        counts[index.min(bins - 1)] += 1;
+    }
+
+    counts.into_iter()
+        .enumerate()
+        .map(|(i, count)| (start + i as f32 * width, width, count))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORDS: &[&str] = &[
+        "castle", "ocean", "forest", "mirror", "staircase", "teacher", "train", "garden",
+        "storm", "bridge", "library", "tiger", "balloon", "desert", "clock", "window",
+    ];
+
+    /// An empty journal directory under the system temp dir, unique to `name` and this run.
+    fn temp_journal(name: &str) -> Journal {
+        let dir = std::env::temp_dir().join(format!("lucid-dreamer-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        Journal::open(&dir).unwrap()
+    }
+
+    fn dream(n: usize) -> Dream {
+        let content: Vec<&str> = (0..12).map(|i| WORDS[(n * 7 + i * 5) % WORDS.len()]).collect();
+        let date = NaiveDate::from_ymd_opt(2024, 1, 1).unwrap() + chrono::Duration::days((n % 400) as i64);
+        serde_json::from_value(serde_json::json!({
+            "id": 0,
+            "date": date.format("%Y-%m-%d").to_string(),
+            "title": format!("Dream {}", n),
+            "content": content.join(" "),
+            "tags": if n.is_multiple_of(3) { vec!["#lucid"] } else { Vec::new() },
+            "lucid": null,
+            "dream_sign": null,
+        })).unwrap()
+    }
+
+    #[test]
+    fn incremental_statistics_match_a_rebuild_under_the_word_cap() {
+        let journal = temp_journal("incremental-stats");
+        let seeded: Vec<Dream> = (0..3000).map(|n| Dream { id: n as u32 + 1, ..dream(n) }).collect();
+        journal.save_dreams(&seeded).unwrap();
+        journal.rebuild_statistics().unwrap();
+        for n in 3000..3050 {
+            journal.add_dream(dream(n)).unwrap();
+        }
+        let incremental = journal.stats().unwrap();
+
+        journal.rebuild_statistics().unwrap();
+        let rebuilt = journal.stats().unwrap();
+        assert_eq!(incremental.total_dreams, 3050);
+        assert_eq!(incremental.total_dreams, rebuilt.total_dreams);
+        assert_eq!(incremental.lucid_dreams, rebuilt.lucid_dreams);
+        assert_eq!(incremental.common_words, rebuilt.common_words);
+        assert_eq!(incremental.dream_calendar, rebuilt.dream_calendar);
+        fs::remove_dir_all(journal.dir()).unwrap();
+    }
+
+    #[test]
+    fn words_dropped_past_the_cap_restart_from_zero() {
+        let journal = temp_journal("word-cap");
+        let word = |i: usize| format!("word{:03}", i);
+        let common: Vec<String> = (0..COMMON_WORDS_KEPT).map(word).collect();
+        let rare: Vec<String> = (COMMON_WORDS_KEPT..COMMON_WORDS_KEPT + 100).map(word).collect();
+        let mut seed: Vec<Dream> = (0..5)
+            .map(|n| Dream { content: common.join(" "), ..dream(n) })
+            .collect();
+        seed.push(Dream { content: rare.join(" "), ..dream(5) });
+        journal.add_dreams(seed).unwrap();
+        let returning = &rare[50];
+        assert!(!journal.stats().unwrap().common_words.contains_key(returning));
+
+        journal.add_dream(Dream { content: [returning.as_str(); 10].join(" "), ..dream(6) }).unwrap();
+        let incremental = journal.stats().unwrap().common_words;
+        journal.rebuild_statistics().unwrap();
+        let rebuilt = journal.stats().unwrap().common_words;
+
+        let mut kept: Vec<&String> = incremental.keys().collect();
+        let mut expected: Vec<&String> = rebuilt.keys().collect();
+        kept.sort();
+        expected.sort();
+        assert_eq!(kept.len(), COMMON_WORDS_KEPT);
+        assert_eq!(kept, expected);
+        assert_eq!(incremental[returning], 10);
+        assert_eq!(rebuilt[returning], 11);
+        for word in kept.iter().filter(|w| w.as_str() != returning) {
+            assert_eq!(incremental[*word], rebuilt[*word], "{}", word);
+        }
+        fs::remove_dir_all(journal.dir()).unwrap();
+    }
+
+    #[test]
+    fn blank_dream_content_is_rejected() {
+        let journal = temp_journal("blank-content");
+        journal.add_dream(dream(1)).unwrap();
+        for content in ["", "   ", " \n\t "] {
+            let err = journal.add_dream(Dream { content: content.to_string(), ..dream(2) }).unwrap_err();
+            assert!(matches!(err.downcast_ref(), Some(JournalError::InvalidInput(_))), "{:?}", content);
+        }
+        let dreams = journal.dreams().unwrap();
+        assert_eq!(dreams.len(), 1);
+        assert_eq!(dreams[0].title, "Dream 1");
+        fs::remove_dir_all(journal.dir()).unwrap();
+    }
+
+    #[test]
+    fn v1_journal_is_upgraded_to_the_current_schema() {
+        let dir = std::env::temp_dir().join(format!("lucid-dreamer-v1-upgrade-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join(DREAMS_FILE), r##"[
+            {"id": 1, "date": "2023-05-01", "title": "Flying", "content": "Over the sea", "tags": ["#lucid"], "lucid": true, "dream_sign": "flying"},
+            {"id": 2, "date": "2023/05/02", "title": "Exam", "content": "Late for class", "tags": [], "lucid": null, "dream_sign": null}
+        ]"##).unwrap();
+
+        let journal = Journal::open(&dir).unwrap();
+        let manifest: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join(MANIFEST_FILE)).unwrap()).unwrap();
+        assert_eq!(manifest["schema_version"], 5);
+
+        let dreams = journal.dreams().unwrap();
+        assert_eq!(dreams.len(), 2);
+        assert_eq!(dreams[1].date, NaiveDate::from_ymd_opt(2023, 5, 2).unwrap());
+        assert!(dreams.iter().all(|dream| dream.links.is_empty()));
+        let stored: serde_json::Value = serde_json::from_str(&fs::read_to_string(dir.join(DREAMS_FILE)).unwrap()).unwrap();
+        assert_eq!(stored[0]["links"], serde_json::json!([]));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn technique_keys() -> Vec<String> {
+        ["MILD", "MILD-Extended", "WBTB", "WILD", "SSILD"].iter().map(|k| k.to_string()).collect()
+    }
+
+    #[test]
+    fn resolve_technique_prefers_a_case_insensitive_exact_match() {
+        assert_eq!(resolve_technique(&technique_keys(), "mild").unwrap(), "MILD");
+        assert_eq!(resolve_technique(&technique_keys(), " wbtb ").unwrap(), "WBTB");
+    }
+
+    #[test]
+    fn resolve_technique_accepts_a_unique_prefix() {
+        assert_eq!(resolve_technique(&technique_keys(), "ss").unwrap(), "SSILD");
+        assert_eq!(resolve_technique(&technique_keys(), "Wb").unwrap(), "WBTB");
+        let err = resolve_technique(&technique_keys(), "dild").unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(JournalError::NotFound(_))));
+        assert_eq!(err.to_string(), "Technique not found: dild");
+    }
+
+    #[test]
+    fn resolve_technique_lists_the_candidates_of_an_ambiguous_prefix() {
+        let err = resolve_technique(&technique_keys(), "w").unwrap_err();
+        assert!(matches!(err.downcast_ref(), Some(JournalError::InvalidInput(_))));
+        assert_eq!(err.to_string(), "Ambiguous technique 'w': could be WBTB, WILD");
+    }
+
+    fn statistics(order: &[&str]) -> Statistics {
+        let mut stats = Statistics::default();
+        for (i, name) in order.iter().enumerate() {
+            let success_rate = if *name == "WBTB" { 0.8 } else { 0.5 };
+            let mut optimal_conditions = HashMap::new();
+            for condition in ["Sleep before practice: ~6h", "Sleep before practice: ~4h", "WBTB awake period: 20 min"] {
+                optimal_conditions.insert(condition.to_string(), 0.5);
+            }
+            stats.technique_effectiveness.insert(name.to_string(), TechniqueStats {
+                attempts: 4, successes: 2, success_rate, optimal_conditions, ..Default::default()
+            });
+            stats.common_words.insert(name.to_lowercase(), 3);
+            stats.dream_calendar.insert(format!("2024-01-0{}", i + 1), 1);
+        }
+        stats
+    }
+
+    #[test]
+    fn statistics_and_rankings_keep_a_stable_order() {
+        let first = statistics(&["WILD", "WBTB", "MILD", "FILD"]);
+        let second = statistics(&["FILD", "MILD", "WBTB", "WILD"]);
+        let json = serde_json::to_string(&first).unwrap();
+        assert_eq!(json, serde_json::to_string(&second).unwrap());
+        assert_eq!(json, serde_json::to_string(&first).unwrap());
+
+        let positions: Vec<usize> = ["\"FILD\"", "\"MILD\"", "\"WBTB\"", "\"WILD\""].iter()
+            .map(|key| json.find(key).unwrap())
+            .collect();
+        assert!(positions.is_sorted());
+
+        for stats in [&first, &second] {
+            let ranked: Vec<&str> = rank_techniques(&stats.technique_effectiveness).iter().map(|(name, _)| name.as_str()).collect();
+            assert_eq!(ranked, ["WBTB", "FILD", "MILD", "WILD"]);
+        }
+    }
+}
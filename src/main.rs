@@ -1,53 +1,200 @@
 use chrono::prelude::*;
-use clap::{Parser, Subcommand, Args};
-use serde::{Deserialize, Serialize};
+use clap::{Parser, Subcommand, Args, ValueEnum};
 use std::{
     collections::HashMap,
     fs,
     io::{self, Write, Read},
-    path::Path,
+    ops::Range,
     process::Command,
     thread,
-    time::{Duration, SystemTime},
+    time::{Duration, Instant},
 };
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use crossterm::event;
-use crossterm::event::{Event, KeyCode};
-use chrono::{Utc, NaiveTime};
-use std::process::Stdio;
-use std::sync::Arc;
-
-const DREAMS_FILE: &str = "dreams.json";
-const CONFIG_FILE: &str = "config.json";
-const STATS_FILE: &str = "stats.json";
-const PROMPTS_FILE: &str = "prompts.txt";
-const DAILY_LOG_FILE: &str = "daily_logs.json";
-const TECHNIQUES_FILE: &str = "techniques.json";
-const ALARMS_FILE: &str = "alarms.json";
-const TECHNIQUE_HISTORY_FILE: &str = "technique_history.json";
+use crossterm::event::{Event, KeyCode, KeyEventKind};
+use crossterm::terminal;
+use chrono::{Local, Utc};
+use std::process::{ExitCode, Stdio};
+use regex::Regex;
+use std::fmt::Write as _;
+use std::io::IsTerminal;
+use std::sync::{Arc, OnceLock};
+use lucid_dreamer::{
+    bucket_by_day, conditions_advice, parse_diary, suggest_tags, sleep_regularity, period_summary, DateRange, AlarmEvent, AlarmEventKind, sign_pairs, SignPair, span_minutes, sleep_cycles, SleepCycle, SLEEP_CYCLE_MINUTES, parse_date, find_terms, anchor_usage, related_dreams, JournalError, search_daily_logs, effectiveness_csv, rank_techniques, control_levels, control_trend, technique_rotation, time_after, median, reality_check_weeks, wbtb_lucidity, lucid_milestone, vividness_by_quality, sleep_debt_hours, daily_log_missing, dry_spell_days, effectiveness_from, word_count, find_duplicate, histogram, longest_streak, next_occurrence, next_trigger, resolve_technique, rem_window, sparkline, suggested_wbtb_time, SearchQuery,
+    technique_history_csv, tokenize, top_ngrams, top_words, typical_schedule, ColorTheme, DailyLog, Dream, DreamStorage, WbtbLucidity, VividnessByQuality, Journal, RealityCheckPrompt, SleepLog, TechniqueData, TechniqueOutcome, TechniqueStats, TimeFormat, RECENCY_HALF_LIFE_DAYS, WBTBAlarm,
+    TREND_DAYS, VIVIDNESS_SCALE, DREAM_SETTINGS, setting_counts, term_trends, percentile, MIN_PERCENTILE_NIGHTS, journal_html, ReportMarker, template_buffer, fill_template, parse_template,
+};
 
 use std::sync::atomic::{AtomicBool, Ordering};
 static ALARM_ACTIVE: AtomicBool = AtomicBool::new(false);
+static ALARM_CANCELED: AtomicBool = AtomicBool::new(false);
+static QUIET: AtomicBool = AtomicBool::new(false);
+
+/// Prints banners, blank spacer lines and nudges, which `--quiet` leaves out.
+macro_rules! decor {
+    ($($arg:tt)*) => {
+        if !QUIET.load(Ordering::Relaxed) {
+            println!($($arg)*);
+        }
+    };
+}
+
+const SOON_ALARM_MINUTES: i64 = 10;
+const SLEEP_HISTOGRAM_BINS: usize = 12;
+const SLEEP_DEBT_DAYS: i64 = 7;
+const REGULARITY_NIGHTS: usize = 14;
+const POOR_REGULARITY_SCORE: u32 = 60;
+const DEFAULT_AWAKE_MINUTES: u32 = 30;
+const DREAM_LENGTH_BIN_WORDS: f32 = 25.0;
+const DREAM_LENGTH_BINS: usize = 8;
+const OPEN_CLEANUP_SECS: u64 = 5;
+const REALITY_CHECK_PAUSE_SECS: u64 = 15;
+const SEARCH_SNIPPET_RADIUS: usize = 40;
+const ANCHOR_NEGLECT_DAYS: i64 = 7;
+const DEFAULT_PER_PAGE: usize = 20;
+const CYCLE_SLOT_MINUTES: i64 = 10;
+const SHORT_BEEP_HZ: f32 = 500.0;
+const SHORT_BEEP_MS: u64 = 300;
+#[cfg(feature = "sound")]
+const TONE_VOLUME: f32 = 0.3;
+
+static THEME: OnceLock<Option<ColorTheme>> = OnceLock::new();
+
+#[derive(Clone, Copy)]
+enum Style {
+    Heading,
+    Section,
+    Value,
+    Subheading,
+    Accent,
+    Bad,
+    Alarm,
+    Highlight,
+}
+
+impl Style {
+    fn code(self, theme: ColorTheme) -> &'static str {
+        match (theme, self) {
+            (ColorTheme::Default, Style::Heading) => "1;34",
+            (ColorTheme::Default, Style::Section) => "1;32",
+            (ColorTheme::Default, Style::Value) => "1;33",
+            (ColorTheme::Default, Style::Subheading) => "1;36",
+            (ColorTheme::Default, Style::Accent) => "1;35",
+            (ColorTheme::Default, Style::Bad) => "1;31",
+            (ColorTheme::Default, Style::Alarm) => "5;31",
+            (ColorTheme::Default, Style::Highlight) => "1;30;43",
+            (ColorTheme::Muted, Style::Heading) => "34",
+            (ColorTheme::Muted, Style::Section) => "32",
+            (ColorTheme::Muted, Style::Value) => "33",
+            (ColorTheme::Muted, Style::Subheading) => "36",
+            (ColorTheme::Muted, Style::Accent) => "35",
+            (ColorTheme::Muted, Style::Bad | Style::Alarm) => "31",
+            (ColorTheme::Muted | ColorTheme::Mono, Style::Highlight) => "7",
+            (ColorTheme::Mono, Style::Alarm) => "1;5",
+            (ColorTheme::Mono, _) => "1",
+        }
+    }
+}
+
+fn init_color(no_color_flag: bool, theme: ColorTheme) {
+    let no_color_env = std::env::var_os("NO_COLOR").is_some_and(|v| !v.is_empty());
+    let enabled = !no_color_flag && !no_color_env && io::stdout().is_terminal();
+    let _ = THEME.set(enabled.then_some(theme));
+}
+
+fn color_enabled() -> bool {
+    matches!(THEME.get(), Some(Some(_)))
+}
+
+fn paint(text: impl std::fmt::Display, style: Style) -> String {
+    match THEME.get() {
+        Some(Some(theme)) => format!("\x1b[{}m{}\x1b[0m", style.code(*theme), text),
+        _ => text.to_string(),
+    }
+}
 
 #[derive(Parser)]
 #[command(name = "Lucid Dreamer")]
 #[command(version = "1.0")]
 #[command(about = "Terminal tool for lucid dream monitoring and training")]
+#[command(after_help = EXIT_CODES_HELP)]
 struct Cli {
+    #[arg(long, global = true)]
+    no_color: bool,
+    /// Print only essential results, without banners, spacing or nudges
+    #[arg(short, long, global = true)]
+    quiet: bool,
+    /// Log every data file read and write, with its absolute path, to stderr
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Seed for random prompt and dream picks, for reproducible runs
+    #[arg(long, global = true, hide = true, env = "LUCID_SEED")]
+    seed: Option<u64>,
+    /// Without a subcommand, an interactive menu is shown
     #[command(subcommand)]
-    command: Commands,
+    command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
 enum Commands {
     Dream(DreamCommands),
     Train(TrainCommands),
-    Stats,
-    Daily,
-    RealityCheck,
+    Stats {
+        #[command(subcommand)]
+        action: Option<StatsActions>,
+        #[arg(long)]
+        rebuild: bool,
+    },
+    Daily(DailyCommands),
+    RealityCheck {
+        #[arg(long)]
+        category: Option<String>,
+        /// Keep running and show a new check every N minutes
+        #[arg(long)]
+        every: Option<u64>,
+        /// Ask whether the check triggered any awareness and log the answer
+        #[arg(long)]
+        log: bool,
+        /// Everyday trigger from `reality_check_anchors` that prompted this check; the check is logged as with --log
+        #[arg(long, conflicts_with = "every")]
+        anchor: Option<String>,
+        /// Number of distinct prompts to go through, with a pause between each
+        #[arg(short = 'n', long, default_value_t = 1)]
+        count: usize,
+    },
     Alarm(AlarmCommands),
-    Analyze,
-    Report,
+    /// Draw a night's estimated sleep cycles and the REM windows best for lucid dreaming
+    SleepCycles {
+        #[arg(short, long)]
+        bedtime: String,
+        #[arg(short, long)]
+        wake: String,
+        /// Length of one sleep cycle in minutes
+        #[arg(long, default_value_t = SLEEP_CYCLE_MINUTES)]
+        cycle_minutes: i64,
+    },
+    Analyze {
+        /// Only analyze what was added since the last `--since-last` run
+        #[arg(long)]
+        since_last: bool,
+    },
+    Report {
+        #[command(subcommand)]
+        period: Option<ReportPeriod>,
+        /// Only count practice sessions from the last N days
+        #[arg(long)]
+        recent_days: Option<i64>,
+        /// Print the effectiveness report as JSON or CSV instead of text
+        #[arg(long, value_enum)]
+        format: Option<ReportFormat>,
+    },
+    Export(ExportCommands),
+    Config(ConfigCommands),
+    Status,
+    /// Check every data file for errors without changing anything
+    Verify,
 }
 
 #[derive(Args)]
@@ -58,10 +205,171 @@ struct DreamCommands {
 
 #[derive(Subcommand)]
 enum DreamActions {
-    Add,
-    List,
+    Add {
+        /// Title to use instead of asking for one
+        #[arg(long)]
+        title: Option<String>,
+        /// Comma-separated tags to use instead of asking for them
+        #[arg(long)]
+        tags: Option<String>,
+        /// Read the content from stdin without any prompts, e.g. from a pipe (needs --title)
+        #[arg(long, requires = "title")]
+        non_interactive: bool,
+        /// Write the content in $EDITOR, starting from the configured dream template
+        #[arg(long, conflicts_with = "non_interactive")]
+        editor: bool,
+    },
+    List {
+        #[arg(long)]
+        limit: Option<usize>,
+        /// Show only this page of results (1-based)
+        #[arg(long)]
+        page: Option<usize>,
+        #[arg(long, default_value_t = DEFAULT_PER_PAGE)]
+        per_page: usize,
+        #[arg(long, value_enum, default_value_t = DreamSort::Id)]
+        sort: DreamSort,
+        #[command(flatten)]
+        filter: DreamFilter,
+        #[arg(long, value_enum, default_value_t = ListFormat::Table)]
+        format: ListFormat,
+    },
     View { id: u32 },
-    Search { keyword: String },
+    /// Open a dream in the system's default markdown viewer
+    Open { id: u32 },
+    /// Show a random dream to revisit
+    Random {
+        #[command(flatten)]
+        filter: DreamFilter,
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+    },
+    Search {
+        keyword: String,
+        /// Treat the keyword as a regular expression matched against title, content and tags
+        #[arg(long)]
+        regex: bool,
+    },
+    Link {
+        id: u32,
+        other_id: u32,
+    },
+    /// List the dreams most like this one, by shared tags and wording
+    Related {
+        id: u32,
+        #[arg(short = 'n', long, default_value_t = 5)]
+        limit: usize,
+    },
+    /// Add more recalled text to the end of a dream
+    Append {
+        id: u32,
+        /// Write the addition in $EDITOR instead of the terminal
+        #[arg(long)]
+        editor: bool,
+    },
+    /// Mark a past dream as lucid (true) or not (false)
+    Lucid {
+        id: u32,
+        #[arg(action = clap::ArgAction::Set)]
+        lucid: bool,
+    },
+    /// Mark a dream as significant, shown with a ★ in lists
+    Star { id: u32 },
+    Unstar { id: u32 },
+    Delete {
+        id: u32,
+        #[arg(long)]
+        yes: bool,
+    },
+    Undo,
+    /// Import dreams from another format
+    Import {
+        #[arg(long, value_enum)]
+        format: ImportFormat,
+        path: String,
+    },
+    /// Convert dreams between dreams.json and one markdown file per dream
+    MigrateStorage {
+        #[arg(long, value_enum)]
+        to: StorageLayout,
+    },
+    /// Replace a tag with another on every dream, e.g. to merge #fly into #flying
+    Retag {
+        #[arg(long)]
+        from: String,
+        /// Tag to use instead; "" removes the tag everywhere
+        #[arg(long)]
+        to: String,
+        /// List the affected dreams without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+    Prune {
+        #[arg(long)]
+        min_words: usize,
+        #[arg(long)]
+        dry_run: bool,
+        #[arg(long)]
+        yes: bool,
+        #[arg(long)]
+        force: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ReportFormat {
+    Json,
+    Csv,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ImportFormat {
+    /// Plain text with `YYYY-MM-DD:` header lines, each followed by a title line and the dream
+    Diary,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum StorageLayout {
+    Json,
+    Files,
+}
+
+#[derive(Args, Default)]
+struct DreamFilter {
+    /// Only dreams with this tag (with or without the #)
+    #[arg(long)]
+    tag: Option<String>,
+    /// Only lucid dreams
+    #[arg(long)]
+    lucid: bool,
+    /// Only starred dreams
+    #[arg(long)]
+    starred: bool,
+}
+
+impl DreamFilter {
+    fn matches(&self, dream: &Dream) -> bool {
+        let normalize = |tag: &str| tag.trim_start_matches('#').to_lowercase();
+        (!self.lucid || dream.lucid == Some(true))
+            && (!self.starred || dream.starred)
+            && self.tag.as_deref().is_none_or(|tag| dream.tags.iter().any(|t| normalize(t) == normalize(tag)))
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ListFormat {
+    /// Fixed-width columns
+    Table,
+    /// GitHub-flavored markdown table
+    Md,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum DreamSort {
+    DateAsc,
+    DateDesc,
+    Id,
+    Title,
 }
 
 #[derive(Args)]
@@ -75,101 +383,161 @@ enum AlarmActions {
     Set {
         #[arg(short, long)]
         bedtime: String,
+        #[arg(short, long, required_unless_present = "preset")]
+        wake_time: Option<String>,
+        /// Minutes to stay awake; defaults to the preset's, or 30
         #[arg(short, long)]
-        wake_time: String,
-        #[arg(short, long, default_value = "30")]
-        awake_minutes: u32,
+        awake_minutes: Option<u32>,
+        /// Work out wake time and awake period from a preset (wbtb-45, wbtb-6h, nap or one from config)
+        #[arg(short, long, conflicts_with = "wake_time")]
+        preset: Option<String>,
+        /// Flash the screen instead of beeping, for shared bedrooms
+        #[arg(long)]
+        silent: bool,
+        /// Add a new alarm even when one with the same bedtime, wake time and awake period exists
+        #[arg(long)]
+        force: bool,
     },
     List,
+    /// Cancel one alarm by id, every alarm with a given wake time, or all of them
     Cancel {
-        id: u32,
+        #[arg(required_unless_present_any = ["all", "wake_time"], conflicts_with_all = ["all", "wake_time"])]
+        id: Option<u32>,
+        /// Cancel every alarm (asks first unless --yes)
+        #[arg(long, conflicts_with = "wake_time")]
+        all: bool,
+        /// Cancel the alarms that wake you at this time (HH:MM)
+        #[arg(short, long)]
+        wake_time: Option<String>,
+        #[arg(long)]
+        yes: bool,
+    },
+    Preview {
+        #[arg(short, long)]
+        wake_time: String,
+    },
+    /// Show what scheduled alarms did: scheduled, triggered, awake period started and completed
+    Log {
+        /// Number of most recent events to print
+        #[arg(short = 'n', long, default_value_t = 20)]
+        lines: usize,
+        /// Keep printing new events as they are written
+        #[arg(short, long)]
+        follow: bool,
     },
 }
 
-#[derive(Args)]
-struct TrainCommands {
-    #[command(subcommand)]
-    technique: Technique,
+#[derive(Subcommand)]
+enum ReportPeriod {
+    /// This week's dream recall, as shown after the daily entry
+    Weekly {
+        /// Cover only what was added since the last `--since-last` run instead of this week
+        #[arg(long)]
+        since_last: bool,
+    },
+    Yearly {
+        #[arg(long)]
+        year: Option<i32>,
+    },
+    /// Weekly reality checks against lucid dream rate
+    RealityChecks,
+    /// Which reality-check anchors you use, and which you have been neglecting
+    Anchors,
+    /// Control level of full-lucid sessions over time, per technique
+    Control {
+        /// Number of recent sessions the trend is computed over
+        #[arg(short, long, default_value_t = 10)]
+        sessions: usize,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct SleepLog {
-    date: String,
-    bedtime: String,
-    wake_time: String,
-    quality: u8,
-    notes: String,
+#[derive(Subcommand)]
+enum StatsActions {
+    /// Compare two periods side by side, e.g. `stats compare last-month this-month`
+    Compare {
+        /// YYYY, YYYY-MM, YYYY-MM-DD, FROM..TO, this-month, last-month, this-week, last-week or last-Nd
+        first: String,
+        second: String,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct DailyLog {
-    date: String,
-    dream: Option<Dream>,
-    sleep: Option<SleepLog>,
-    wake_feeling: Option<String>,
-    reality_checks: u32,
-    notes: String,
-    technique_practice: Option<TechniquePractice>,
-    wbtb_alarm_used: Option<u32>,
+#[derive(Args)]
+struct DailyCommands {
+    #[command(subcommand)]
+    action: Option<DailyActions>,
+    /// How the wake feeling and notes are written
+    #[arg(long, value_enum, default_value_t = NotesInput::Line)]
+    notes: NotesInput,
+    /// Go through every prompt without saving anything, to try the flow out
+    #[arg(long)]
+    dry_run: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct Dream {
-    id: u32,
-    date: String,
-    title: String,
-    content: String,
-    tags: Vec<String>,
-    lucid: Option<bool>,
-    dream_sign: Option<String>,
+#[derive(ValueEnum, Clone, Copy)]
+enum NotesInput {
+    /// One line each, ended with Enter
+    Line,
+    /// As many lines as you like, ended with Ctrl+D
+    Multiline,
+    /// Written in $EDITOR
+    Editor,
 }
 
-#[derive(Serialize, Deserialize, Default)]
-struct Config {
-    reality_check_prompts: Vec<String>,
+#[derive(Subcommand)]
+enum DailyActions {
+    /// Search daily-log notes, wake feelings, tags and dreams (case-insensitive)
+    Search {
+        keyword: String,
+    },
+    /// Show a day's log (default today) with the night's estimated sleep cycles
+    View {
+        /// Date as YYYY-MM-DD
+        date: Option<String>,
+    },
+    /// Merge daily logs that share a date into one log per date
+    Repair,
 }
 
-#[derive(Serialize, Deserialize, Default)]
-struct Statistics {
-    total_dreams: u32,
-    lucid_dreams: u32,
-    common_words: HashMap<String, u32>,
-    dream_calendar: HashMap<String, u32>,
-    technique_effectiveness: HashMap<String, TechniqueStats>,
+#[derive(Args)]
+struct ConfigCommands {
+    #[command(subcommand)]
+    action: ConfigActions,
 }
 
-#[derive(Serialize, Deserialize, Default, Debug)]
-struct TechniqueStats {
-    attempts: u32,
-    successes: u32,
-    last_practiced: String,
-    success_rate: f32,
-    optimal_conditions: HashMap<String, f32>,
+#[derive(Subcommand)]
+enum ConfigActions {
+    /// Print the effective configuration, defaults included
+    Show,
+    /// Change one setting in config.json, e.g. `config set time_format 12h`
+    Set {
+        key: String,
+        value: String,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct TechniqueData {
-    name: String,
-    description: String,
-    steps: Vec<String>,
-    last_practiced: Option<String>,
+#[derive(Args)]
+struct ExportCommands {
+    #[command(subcommand)]
+    action: ExportActions,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct TechniquePractice {
-    technique: String,
-    date: String,
-    duration_minutes: u32,
-    outcome: TechniqueOutcome,
+#[derive(Subcommand)]
+enum ExportActions {
+    Techniques {
+        #[arg(short, long)]
+        out: Option<String>,
+    },
+    /// The whole journal as one self-contained HTML page, viewable in any browser
+    Html {
+        #[arg(short, long, default_value = "journal.html")]
+        out: String,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-#[serde(tag = "type", content = "data")]
-enum TechniqueOutcome {
-    Unattempted,
-    Failed,
-    PartialLucid,
-    FullLucid { control_level: u8 },
+#[derive(Args)]
+struct TrainCommands {
+    #[command(subcommand)]
+    technique: Technique,
 }
 
 #[derive(Subcommand, Clone)]
@@ -178,65 +546,144 @@ enum Technique {
     Wbtb,
     Fild,
     Rc,
+    /// Practice a technique by name; case-insensitive, prefixes allowed
+    Practice {
+        name: String,
+        /// Run a countdown of this many minutes instead of an open-ended timer; any key still ends early
+        #[arg(short, long)]
+        duration: Option<u64>,
+        /// Beep when the countdown finishes
+        #[arg(long, requires = "duration")]
+        beep: bool,
+    },
+    /// List every technique with its description
+    List,
+    /// Show a technique's full steps without starting a practice session; case-insensitive, prefixes allowed
+    View {
+        name: String,
+    },
+    /// Show how long ago each technique was practiced and which are due
+    Status {
+        /// Days without practice before a technique is due (default from config)
+        #[arg(short, long)]
+        days: Option<u32>,
+    },
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone)]
-struct WBTBAlarm {
-    id: u32,
-    bedtime: String,
-    wake_time: String,
-    awake_minutes: u32,
-    enabled: bool,
-    last_triggered: Option<String>,
-    success: Option<bool>,
-}
-
-fn schedule_alarm(wake_time: &str, awake_minutes: u32) -> anyhow::Result<()> {
-    let now = Utc::now();
-    let wake_naive = NaiveTime::parse_from_str(wake_time, "%H:%M")?;
-    
-    let today = now.date_naive();
-    let wake_today = today.and_time(wake_naive);
-    let wake_utc = Utc.from_utc_datetime(&wake_today);
-    
-    let duration = if wake_utc > now {
-        wake_utc - now
-    } else {
-        let tomorrow = today.succ_opt().unwrap();
-        let wake_tomorrow = tomorrow.and_time(wake_naive);
-        Utc.from_utc_datetime(&wake_tomorrow) - now
-    };
-    
-    let secs = duration.num_seconds() as u64;
+fn schedule_alarm(journal: &Journal, alarm: &WBTBAlarm) -> anyhow::Result<()> {
+    let trigger_at = next_trigger(alarm)?;
+    let secs = (trigger_at - Local::now()).num_seconds().max(0) as u64;
     
     println!("Alarm scheduled to trigger in {} seconds", secs);
+    let time_format = journal.config()?.time_format;
+    println!("Will trigger at {} local", time_format.format_datetime(&trigger_at));
+    journal.record_alarm_event(alarm.id, AlarmEventKind::Scheduled,
+        &format!("fires at {}", time_format.format_datetime(&trigger_at)))?;
     
-    let wake_time = wake_time.to_string();
+    let alarm = alarm.clone();
+    let journal = journal.clone();
     thread::spawn(move || {
         thread::sleep(Duration::from_secs(secs));
-        trigger_alarm(&wake_time, awake_minutes);
+        // `alarm cancel` only edits alarms.json, so check the alarm is still there before firing
+        let canceled = journal.alarms().is_ok_and(|alarms| !alarms.iter().any(|a| a.id == alarm.id));
+        if canceled {
+            let _ = journal.record_alarm_event(alarm.id, AlarmEventKind::Canceled, "canceled before it fired");
+            ALARM_CANCELED.store(true, Ordering::Relaxed);
+            return;
+        }
+        trigger_alarm(&journal, &alarm);
     });
     
     Ok(())
 }
 
-fn load_alarms() -> anyhow::Result<Vec<WBTBAlarm>> {
-    if !Path::new(ALARMS_FILE).exists() {
-        return Ok(Vec::new());
-    }
-    let data = fs::read_to_string(ALARMS_FILE)?;
-    Ok(serde_json::from_str(&data)?)
+fn preview_alarm(journal: &Journal, wake_time: &str) -> anyhow::Result<()> {
+    let trigger_at = next_occurrence(wake_time)?;
+    let time_format = journal.config()?.time_format;
+    let minutes = (trigger_at - Local::now()).num_minutes();
+    
+    println!("Will trigger at {} local (in {}h {}m)",
+        time_format.format_datetime(&trigger_at), minutes / 60, minutes % 60);
+    println!("Nothing was scheduled.");
+    
+    Ok(())
 }
 
-fn save_alarms(alarms: &[WBTBAlarm]) -> anyhow::Result<()> {
-    let data = serde_json::to_string_pretty(alarms)?;
-    fs::write(ALARMS_FILE, data)?;
+fn alarm_log(journal: &Journal, lines: usize, follow: bool) -> anyhow::Result<()> {
+    let print_event = |event: &AlarmEvent| {
+        let line = format!("{}  #{:<3} {:<14} {}", event.timestamp, event.alarm_id, event.kind.label(), event.detail);
+        println!("{}", line.trim_end());
+    };
+    
+    let events = journal.alarm_log()?;
+    if events.is_empty() && !follow {
+        println!("No alarm events logged yet.");
+    }
+    for event in &events[events.len().saturating_sub(lines)..] {
+        print_event(event);
+    }
+    
+    if follow {
+        let mut seen = events.len();
+        loop {
+            thread::sleep(Duration::from_millis(500));
+            let events = journal.alarm_log()?;
+            for event in events.iter().skip(seen) {
+                print_event(event);
+            }
+            seen = events.len();
+        }
+    }
     Ok(())
 }
 
-fn set_wbtb_alarm(bedtime: &str, wake_time: &str, awake_minutes: u32) -> anyhow::Result<()> {
-    let mut alarms = load_alarms()?;
-    let id = alarms.last().map_or(1, |a| a.id + 1);
+/// Sets and schedules an alarm. An existing alarm with the same schedule is reused (and re-enabled)
+/// rather than duplicated, unless `force` is set.
+fn set_wbtb_alarm(journal: &Journal, bedtime: &str, wake_time: &str, awake_minutes: u32, silent: bool, force: bool) -> anyhow::Result<bool> {
+    let trigger_at = next_occurrence(wake_time)?;
+    let now = Local::now();
+    let minutes_until = (trigger_at - now).num_minutes();
+    
+    if trigger_at.date_naive() != now.date_naive() {
+        println!("Note: {} has already passed today, so the alarm is set for tomorrow ({}).",
+            wake_time, trigger_at.format("%Y-%m-%d"));
+    }
+    
+    let config = journal.config()?;
+    let (min_hours, max_hours) = (config.wbtb_min_sleep_hours, config.wbtb_max_sleep_hours);
+    if let Some(minutes) = span_minutes(bedtime, wake_time) {
+        let hours = minutes as f32 / 60.0;
+        if hours < min_hours || hours > max_hours {
+            println!("{}", paint(format!("{} {:.1}h of sleep before WBTB — techniques work best after {}-{}h.",
+                if hours < min_hours { "Only" } else { "A full" }, hours, min_hours, max_hours), Style::Bad));
+            let answer = prompt("CONFIRM", "Set it anyway? (y/N): ")?;
+            if !answer.trim().eq_ignore_ascii_case("y") {
+                println!("Alarm not set.");
+                return Ok(false);
+            }
+        }
+    }
+    
+    if minutes_until < SOON_ALARM_MINUTES {
+        let answer = prompt("CONFIRM", &format!("This alarm fires in {} minutes — continue? (y/N): ", minutes_until))?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Alarm not set.");
+            return Ok(false);
+        }
+    }
+    
+    let mut alarms = journal.alarms()?;
+    if !force && let Some(existing) = alarms.iter_mut().find(|a| a.same_schedule(bedtime, wake_time, awake_minutes)) {
+        existing.enabled = true;
+        existing.silent = silent;
+        let existing = existing.clone();
+        journal.save_alarms(&alarms)?;
+        println!("Alarm #{} already wakes you at {} after a {} bedtime; using it instead of adding a duplicate (--force adds another).",
+            existing.id, existing.wake_time, existing.bedtime);
+        schedule_alarm(journal, &existing)?;
+        return Ok(true);
+    }
+    let id = journal.next_alarm_id()?;
     
     let new_alarm = WBTBAlarm {
         id,
@@ -246,61 +693,124 @@ fn set_wbtb_alarm(bedtime: &str, wake_time: &str, awake_minutes: u32) -> anyhow:
         enabled: true,
         last_triggered: None,
         success: None,
+        success_inferred: false,
+        silent,
     };
     
-    alarms.push(new_alarm);
-    save_alarms(&alarms)?;
+    alarms.push(new_alarm.clone());
+    journal.save_alarms(&alarms)?;
     
     println!("WBTB alarm set for bedtime: {}, wake at: {}, awake for {} minutes", 
         bedtime, wake_time, awake_minutes);
     
-    schedule_alarm(wake_time, awake_minutes)?;
+    schedule_alarm(journal, &new_alarm)?;
     
-    Ok(())
+    Ok(true)
 }
 
 
-fn list_alarms() -> anyhow::Result<()> {
-    let alarms = load_alarms()?;
+fn list_alarms(journal: &Journal) -> anyhow::Result<()> {
+    let alarms = journal.alarms()?;
+    let time_format = journal.config()?.time_format;
     if alarms.is_empty() {
         println!("No active alarms");
         return Ok(());
     }
 
-    println!("{:<5} {:<10} {:<10} {:<8}", "ID", "Sleep time", "Wake time", "Awake time");
+    println!("{:<5} {:<10} {:<10} {:<12} {:<20} {:<14} Success",
+        "ID", "Sleep time", "Wake time", "Awake time", "Next trigger", "Last triggered");
     for alarm in alarms {
-        println!("{:<5} {:<10} {:<10} {:<8} min", 
+        let next = next_trigger(&alarm)
+            .map(|t| time_format.format_datetime(&t))
+            .unwrap_or_else(|_| "invalid time".to_string());
+        let success = match alarm.success {
+            Some(true) if alarm.success_inferred => "✓ (lucid night)",
+            Some(true) => "✓",
+            Some(false) => "✗",
+            None => "—",
+        };
+        println!("{:<5} {:<10} {:<10} {:<12} {:<20} {:<14} {}{}",
             alarm.id, 
-            alarm.bedtime, 
-            alarm.wake_time, 
-            alarm.awake_minutes);
+            time_format.format_str(&alarm.bedtime), 
+            time_format.format_str(&alarm.wake_time), 
+            format!("{} min", alarm.awake_minutes),
+            next,
+            alarm.last_triggered.map_or_else(|| "never".to_string(), |d| d.to_string()),
+            success,
+            if alarm.silent { "  (silent)" } else { "" });
     }
     
     Ok(())
 }
 
-fn cancel_alarm(id: u32) -> anyhow::Result<()> {
-    let mut alarms = load_alarms()?;
-    if let Some(index) = alarms.iter().position(|a| a.id == id) {
-        alarms.remove(index);
-        save_alarms(&alarms)?;
-        println!("Alarm #{} canceled.", id);
-    } else {
-        println!("Alarm #{} not found.", id);
+fn cancel_alarm(journal: &Journal, id: Option<u32>, all: bool, wake_time: Option<&str>, yes: bool) -> anyhow::Result<()> {
+    let mut alarms = journal.alarms()?;
+    let wake_time = wake_time
+        .map(|time| NaiveTime::parse_from_str(time, "%H:%M")
+            .map_err(|_| JournalError::InvalidInput(format!("Invalid wake time '{}', expected HH:MM", time))))
+        .transpose()?;
+    let matches = |alarm: &WBTBAlarm| {
+        all || id == Some(alarm.id)
+            || wake_time.is_some_and(|time| NaiveTime::parse_from_str(&alarm.wake_time, "%H:%M") == Ok(time))
+    };
+    
+    let count = alarms.iter().filter(|a| matches(a)).count();
+    if count == 0 {
+        anyhow::bail!(JournalError::NotFound(match id {
+            Some(id) => format!("Alarm #{} not found", id),
+            None => "No matching alarms".to_string(),
+        }));
+    }
+    
+    if all && !yes {
+        let answer = prompt("CONFIRM", &format!("Cancel every alarm ({} in total)? (y/n): ", count))?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Nothing was canceled.");
+            return Ok(());
+        }
+    }
+    
+    alarms.retain(|a| !matches(a));
+    journal.save_alarms(&alarms)?;
+    match (id, count) {
+        (Some(id), _) => println!("Alarm #{} canceled.", id),
+        (None, 1) => println!("Canceled 1 alarm."),
+        (None, count) => println!("Canceled {} alarms.", count),
     }
     Ok(())
 }
 
-fn trigger_alarm(wake_time: &str, awake_minutes: u32) {
+fn trigger_alarm(journal: &Journal, alarm: &WBTBAlarm) {
     ALARM_ACTIVE.store(true, Ordering::Relaxed);
+    if let Ok(mut alarms) = journal.alarms()
+        && let Some(stored) = alarms.iter_mut().find(|a| a.id == alarm.id) {
+        stored.last_triggered = Some(Utc::now().date_naive());
+        let _ = journal.save_alarms(&alarms);
+    }
+    let awake_minutes = alarm.awake_minutes;
+    let silent = alarm.silent;
     
-    println!("\n\x1b[5;31m!!! WBTB ALARM !!!\x1b[0m");
+    println!("\n{}", paint("!!! WBTB ALARM !!!", Style::Alarm));
     println!("Wake Back to Bed Technique Time!");
     println!("Stay awake for {} minutes", awake_minutes);
     
-    play_alarm_sound();
+    if !silent {
+        let config = journal.config().unwrap_or_default();
+        play_alarm_sound(config.alarm_tone_hz, Duration::from_millis(config.alarm_tone_ms));
+    }
+    if journal.config().is_ok_and(|c| c.desktop_notifications) {
+        notify("WBTB alarm", &format!("Time to wake up! Stay awake for {} minutes.", awake_minutes));
+    }
     
-    for _ in 0..10 {
+    // Silent alarms rely on the flash alone, so they flash longer and even without colors.
+    let flashes = if silent && io::stdout().is_terminal() {
+        30
+    } else if color_enabled() {
+        10
+    } else {
+        0
+    };
+    for _ in 0..flashes {
         print!("\x1b[?5h");
         io::stdout().flush().unwrap();
         thread::sleep(Duration::from_millis(200));
@@ -309,33 +819,71 @@ fn trigger_alarm(wake_time: &str, awake_minutes: u32) {
         thread::sleep(Duration::from_millis(200));
     }
     
-    println!("\nAlarm triggered at {}", wake_time);
+    println!("\nAlarm triggered at {}", alarm.wake_time);
+    let _ = journal.record_alarm_event(alarm.id, AlarmEventKind::Triggered, if silent { "silent" } else { "" });
     
     let awake_minutes = Arc::new(awake_minutes);
     let awake_minutes_clone = Arc::clone(&awake_minutes);
+    let activities = journal.config().map(|c| c.wbtb_activities).unwrap_or_default();
+    let journal = journal.clone();
+    let alarm_id = alarm.id;
     
     thread::spawn(move || {
-        println!("\n\x1b[1;34mAWAKE PERIOD STARTED\x1b[0m");
+        decor!("\n{}", paint("AWAKE PERIOD STARTED", Style::Heading));
+        let _ = journal.record_alarm_event(alarm_id, AlarmEventKind::AwakeStarted,
+            &format!("{} minutes", awake_minutes_clone));
         println!("You have {} minutes to stay awake", awake_minutes_clone);
         
-        for min in (1..=*awake_minutes_clone).rev() {
-            println!("{} minutes remaining...", min);
+        for (elapsed, min) in (1..=*awake_minutes_clone).rev().enumerate() {
+            if activities.is_empty() {
+                println!("{} minutes remaining...", min);
+            } else {
+                let activity = &activities[elapsed % activities.len()];
+                println!("{} minutes remaining... Now: {}", min, activity);
+            }
             thread::sleep(Duration::from_secs(60));
         }
         
-        println!("\n\x1b[1;32mTIME TO RETURN TO SLEEP!\x1b[0m");
+        println!("\n{}", paint("TIME TO RETURN TO SLEEP!", Style::Section));
         println!("Lie down, relax, and perform your lucid dream technique");
         println!("Good luck with your lucid dream!");
         
-        play_return_to_sleep_sound();
+        if !silent {
+            play_short_beep();
+        }
+        let _ = journal.record_alarm_event(alarm_id, AlarmEventKind::Completed, "");
         ALARM_ACTIVE.store(false, Ordering::Relaxed);
     });
 }
 
-fn play_return_to_sleep_sound() {
+/// Plays a sine tone on the default output device, returning once it has finished.
+/// False when no device could be opened, or without the `sound` feature.
+#[cfg(feature = "sound")]
+fn play_tone(frequency: f32, duration: Duration) -> bool {
+    use rodio::source::{SineWave, Source};
+    
+    let Ok(mut device) = rodio::DeviceSinkBuilder::open_default_sink() else {
+        return false;
+    };
+    device.log_on_drop(false);
+    let player = rodio::Player::connect_new(device.mixer());
+    player.append(SineWave::new(frequency).take_duration(duration).amplify(TONE_VOLUME));
+    player.sleep_until_end();
+    true
+}
+
+#[cfg(not(feature = "sound"))]
+fn play_tone(_frequency: f32, _duration: Duration) -> bool {
+    false
+}
+
+fn play_short_beep() {
+    if play_tone(SHORT_BEEP_HZ, Duration::from_millis(SHORT_BEEP_MS)) {
+        return;
+    }
     if cfg!(target_os = "windows") {
         let _ = Command::new("powershell")
-            .args(&["-c", "[console]::beep(500, 300)"])
+            .args(["-c", &format!("[console]::beep({}, {})", SHORT_BEEP_HZ, SHORT_BEEP_MS)])
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .status();
@@ -345,10 +893,15 @@ fn play_return_to_sleep_sound() {
     }
 }
 
-fn play_alarm_sound() {
+/// Sounds the alarm tone, falling back to the system's sound player and the terminal bell
+/// when no tone can be played.
+fn play_alarm_sound(frequency: f32, duration: Duration) {
+    if play_tone(frequency, duration) {
+        return;
+    }
     if cfg!(target_os = "windows") {
         let _ = Command::new("powershell")
-            .args(&["-c", "[console]::beep(1000, 1000)"])
+            .args(["-c", &format!("[console]::beep({}, {})", frequency as u32, duration.as_millis())])
             .stdout(Stdio::null())
             .stderr(Stdio::null())
             .status();
@@ -372,84 +925,72 @@ fn play_alarm_sound() {
     }
 }
 
-fn load_technique_history() -> anyhow::Result<Vec<TechniquePractice>> {
-    if !Path::new(TECHNIQUE_HISTORY_FILE).exists() {
-        return Ok(Vec::new());
+fn generate_effectiveness_report(journal: &Journal, recent_days: Option<i64>, format: Option<ReportFormat>) -> anyhow::Result<()> {
+    let today = Utc::now().date_naive();
+    let history: Vec<_> = journal.technique_history()?.into_iter()
+        .filter(|p| recent_days.is_none_or(|days| {
+            (today - p.date).num_days() < days
+        }))
+        .collect();
+    let stats = effectiveness_from(&history, &journal.daily_logs()?, &journal.alarms()?, today);
+    
+    match format {
+        Some(ReportFormat::Json) => {
+            let ranked = rank_techniques(&stats);
+            let entry = |(technique, data): (&String, &TechniqueStats)| serde_json::json!({
+                "technique": technique,
+                "success_rate": data.success_rate,
+            });
+            let comparison = (ranked.len() > 1).then(|| serde_json::json!({
+                "most_effective": entry(ranked[0]),
+                "least_effective": entry(ranked[ranked.len() - 1]),
+            }));
+            // Most effective first, like the text report, rather than in hash order.
+            let techniques = ranked.iter()
+                .map(|(technique, data)| Ok(((*technique).clone(), serde_json::to_value(data)?)))
+                .collect::<serde_json::Result<serde_json::Map<_, _>>>()?;
+            let report = serde_json::json!({
+                "recent_days": recent_days,
+                "techniques": techniques,
+                "comparison": comparison,
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        }
+        Some(ReportFormat::Csv) => print!("{}", effectiveness_csv(&stats)),
+        None => print_effectiveness_report(journal, &stats, recent_days)?,
     }
     
-    let data = fs::read_to_string(TECHNIQUE_HISTORY_FILE)?;
-    let history = serde_json::from_str(&data).unwrap_or_else(|_| Vec::new());
-    Ok(history)
-}
-
-fn record_technique_practice(technique: &str, outcome: TechniqueOutcome, duration_minutes: u32) -> anyhow::Result<()> {
-    let mut history = load_technique_history().unwrap_or_default();
-    
-    let practice = TechniquePractice {
-        technique: technique.to_string(),
-        date: Utc::now().format("%Y-%m-%d").to_string(),
-        duration_minutes,
-        outcome,
-    };
-    
-    history.push(practice);
-    let data = serde_json::to_string_pretty(&history)?;
-    fs::write(TECHNIQUE_HISTORY_FILE, data)?;
+    // A trailing window is a view, not the full picture worth caching.
+    if recent_days.is_none() {
+        let mut all_stats = journal.stats()?;
+        all_stats.technique_effectiveness = stats;
+        journal.save_stats(&all_stats)?;
+    }
     
     Ok(())
 }
 
-fn calculate_technique_effectiveness() -> anyhow::Result<HashMap<String, TechniqueStats>> {
-    let history = load_technique_history()?;
-    let mut stats: HashMap<String, TechniqueStats> = HashMap::new();
-    
-    for practice in history {
-        let entry = stats.entry(practice.technique.clone()).or_insert_with(|| TechniqueStats {
-            attempts: 0,
-            successes: 0,
-            last_practiced: practice.date.clone(),
-            success_rate: 0.0,
-            optimal_conditions: HashMap::new(),
-        });
-        
-        entry.attempts += 1;
-        
-        match practice.outcome {
-            TechniqueOutcome::PartialLucid | TechniqueOutcome::FullLucid { .. } => {
-                entry.successes += 1;
-            }
-            _ => {}
-        }
-        
-        if entry.attempts > 0 {
-            entry.success_rate = (entry.successes as f32 / entry.attempts as f32) * 100.0;
-        }
+fn print_effectiveness_report(journal: &Journal, stats: &HashMap<String, TechniqueStats>, recent_days: Option<i64>) -> anyhow::Result<()> {
+    decor!("\n{}", paint("LUCID DREAM TECHNIQUE EFFECTIVENESS REPORT", Style::Heading));
+    if let Some(days) = recent_days {
+        decor!("Sessions from the last {} days", days);
     }
-    
-    for stat in stats.values_mut() {
-        for value in stat.optimal_conditions.values_mut() {
-            *value = (*value / stat.successes as f32) * 100.0;
-        }
-    }
-    
-    Ok(stats)
-}
-fn generate_effectiveness_report() -> anyhow::Result<()> {
-    let stats = calculate_technique_effectiveness()?;
-    
-    println!("\n\x1b[1;34mLUCID DREAM TECHNIQUE EFFECTIVENESS REPORT\x1b[0m");
-    println!("===============================================\n");
-    
-    for (technique, data) in &stats {
-        println!("\x1b[1;32m{} Technique\x1b[0m", technique);
-        println!("  Success Rate: \x1b[1;33m{:.1}%\x1b[0m ({} successes / {} attempts)", 
-            data.success_rate, data.successes, data.attempts);
+    decor!("===============================================\n");
+    
+    for (technique, data) in rank_techniques(stats) {
+        println!("{}", paint(format!("{} Technique", technique), Style::Section));
+        println!("  Success Rate: {} ({} successes / {} attempts)", 
+            paint(format!("{:.1}%", data.success_rate), Style::Value), data.successes, data.attempts);
+        println!("  Recent-weighted Rate: {} (sessions count half after {} days)",
+            paint(format!("{:.1}%", data.weighted_success_rate), Style::Value), RECENCY_HALF_LIFE_DAYS);
+        println!("  Time Practiced: {} for {} successes (avg {:.0} min per session)",
+            paint(format!("{}h {}m", data.total_minutes / 60, data.total_minutes % 60), Style::Value), data.successes, data.average_minutes);
         println!("  Last Practiced: {}", data.last_practiced);
         
         if !data.optimal_conditions.is_empty() {
-            println!("\n  \x1b[1;36mOptimal Conditions:\x1b[0m");
-            for (condition, rate) in &data.optimal_conditions {
-                println!("    - {}: {:.1}% success rate", condition, rate);
+            println!("\n  {}", paint("Optimal Conditions:", Style::Subheading));
+            for (condition, rate) in data.ranked_conditions() {
+                println!("    - {}: {:.0}% of successes", condition, rate);
             }
         }
         
@@ -461,379 +1002,1401 @@ fn generate_effectiveness_report() -> anyhow::Result<()> {
             "Try modifying approach or switch techniques".to_string()
         };
         
-        println!("\n  \x1b[1;35mRecommendation:\x1b[0m {}", recommendation);
-        println!();
+        println!("\n  {} {}", paint("Recommendation:", Style::Accent), recommendation);
+        if let Some(advice) = conditions_advice(data) {
+            println!("  {} {}.", technique, advice);
+        }
+        decor!();
     }
     
     if stats.len() > 1 {
-        println!("\x1b[1;34mTECHNIQUE COMPARISON\x1b[0m");
-        let mut sorted: Vec<_> = stats.iter().collect();
-        sorted.sort_by(|a, b| b.1.success_rate.partial_cmp(&a.1.success_rate).unwrap());
+        decor!("{}", paint("TECHNIQUE COMPARISON", Style::Heading));
+        let sorted = rank_techniques(stats);
         
-        println!("  Most Effective: \x1b[1;32m{}\x1b[0m ({:.1}% success)", 
-            sorted[0].0, sorted[0].1.success_rate);
-        println!("  Least Effective: \x1b[1;31m{}\x1b[0m ({:.1}% success)", 
-            sorted.last().unwrap().0, sorted.last().unwrap().1.success_rate);
+        println!("  Most Effective: {} ({:.1}% success)", 
+            paint(sorted[0].0, Style::Section), sorted[0].1.success_rate);
+        println!("  Least Effective: {} ({:.1}% success)", 
+            paint(sorted.last().unwrap().0, Style::Bad), sorted.last().unwrap().1.success_rate);
     }
     
-    let mut all_stats: Statistics = if Path::new(STATS_FILE).exists() {
-        let data = fs::read_to_string(STATS_FILE)?;
-        serde_json::from_str(&data)?
-    } else {
-        Statistics::default()
-    };
-    
-    all_stats.technique_effectiveness = stats;
-    let data = serde_json::to_string_pretty(&all_stats)?;
-    fs::write(STATS_FILE, data)?;
+    print_wbtb_lucidity(&wbtb_lucidity(&journal.daily_logs()?, &journal.alarms()?));
+    print_vividness_by_quality(&vividness_by_quality(&journal.dreams()?, &journal.daily_logs()?), journal.config()?.quality_scale);
     
     Ok(())
 }
 
-fn wait_for_keypress() -> anyhow::Result<()> {
-    loop {
-        if let Event::Key(event) = event::read()? {
-            if event.code != KeyCode::Null {
-                break;
-            }
-        }
+fn print_vividness_by_quality(comparison: &VividnessByQuality, scale: u8) {
+    if comparison.by_quality.is_empty() {
+        return;
+    }
+    
+    decor!("\n{}", paint("RECALL VIVIDNESS AND SLEEP QUALITY", Style::Heading));
+    for &(quality, vividness, dreams) in &comparison.by_quality {
+        println!("  Sleep quality {}/{}: vividness {:.1}/{} ({} dreams)", quality, scale, vividness, VIVIDNESS_SCALE, dreams);
+    }
+    if let Some(r) = comparison.correlation {
+        let strength = match r.abs() {
+            r if r >= 0.5 => "strongly",
+            r if r >= 0.3 => "moderately",
+            r if r >= 0.1 => "weakly",
+            _ => "barely",
+        };
+        let direction = if r >= 0.0 { "better sleep, more vivid recall" } else { "better sleep, less vivid recall" };
+        println!("  Correlation: r = {:.2} ({} linked: {})", r, strength, direction);
     }
-    Ok(())
 }
 
-fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
-    let mut should_wait = false;
+fn print_wbtb_lucidity(comparison: &WbtbLucidity) {
+    let rate = |(lucid, nights): (u32, u32)| lucid as f32 / nights.max(1) as f32 * 100.0;
+    let (with, without) = (comparison.with_wbtb, comparison.without_wbtb);
+    if with.1 == 0 {
+        return;
+    }
+    
+    decor!("\n{}", paint("WBTB AND LUCIDITY", Style::Heading));
+    println!("  WBTB nights: {:.0}% lucid ({} of {}) vs {:.0}% otherwise ({} of {})",
+        rate(with), with.0, with.1, rate(without), without.0, without.1);
+    
+    if comparison.by_awake_minutes.len() > 1 {
+        println!("  By time awake:");
+        for &(minutes, lucid, nights) in &comparison.by_awake_minutes {
+            println!("    {:>3} min: {:.0}% lucid ({} of {})", minutes, rate((lucid, nights)), lucid, nights);
+        }
+    }
+    if let Some(best) = comparison.by_awake_minutes.iter()
+        .filter(|(_, lucid, _)| *lucid > 0)
+        .max_by(|a, b| rate((a.1, a.2)).total_cmp(&rate((b.1, b.2))).then(b.0.cmp(&a.0))) {
+        println!("  Best awake window so far: {} minutes", best.0);
+    }
+}
 
-    if let Commands::Alarm(AlarmCommands { action: AlarmActions::Set { bedtime, wake_time, awake_minutes } }) = &cli.command {
-        set_wbtb_alarm(bedtime, wake_time, *awake_minutes)?;
-        should_wait = true;
+fn yearly_report(journal: &Journal, year: Option<i32>) -> anyhow::Result<()> {
+    let year = year.unwrap_or_else(|| Utc::now().year());
+    let in_year = |date: &NaiveDate| date.year() == year;
+    
+    let all_dreams = journal.dreams()?;
+    let dreams: Vec<&Dream> = all_dreams.iter().filter(|d| in_year(&d.date)).collect();
+    let logs: Vec<DailyLog> = journal.daily_logs()?.into_iter().filter(|l| in_year(&l.date)).collect();
+    let history: Vec<_> = journal.technique_history()?.into_iter().filter(|p| in_year(&p.date)).collect();
+    
+    decor!("\n{}", paint(format!("YOUR {} IN DREAMS", year), Style::Heading));
+    decor!("===============================================");
+    
+    if dreams.is_empty() && logs.is_empty() && history.is_empty() {
+        println!("\nNo entries recorded in {}.", year);
+        return Ok(());
+    }
+    
+    let lucid = dreams.iter().filter(|d| d.lucid == Some(true)).count();
+    println!("\n{}", paint("Dreams", Style::Section));
+    println!("  Dreams recorded: {}", paint(dreams.len(), Style::Value));
+    println!("  Lucid dreams: {} ({:.1}%)",
+        paint(lucid, Style::Value),
+        if dreams.is_empty() { 0.0 } else { lucid as f32 / dreams.len() as f32 * 100.0 });
+    println!("  Longest recall streak: {} days",
+        longest_streak(dreams.iter().map(|d| d.date)));
+    
+    let mut tag_counts: HashMap<&str, u32> = HashMap::new();
+    for tag in dreams.iter().flat_map(|d| &d.tags) {
+        *tag_counts.entry(tag.as_str()).or_insert(0) += 1;
+    }
+    let mut top_tags: Vec<_> = tag_counts.into_iter().collect();
+    top_tags.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    if !top_tags.is_empty() {
+        let tags: Vec<String> = top_tags.iter().take(5).map(|(t, c)| format!("{} ({})", t, c)).collect();
+        println!("  Top tags: {}", tags.join(", "));
+    }
+    
+    let words = top_words(dreams.iter().map(|d| d.content.as_str()), 5);
+    if !words.is_empty() {
+        let words: Vec<String> = words.iter().map(|(w, c)| format!("{} ({})", w, c)).collect();
+        println!("  Top words: {}", words.join(", "));
+    }
+    
+    println!("\n{}", paint("Practice", Style::Section));
+    let effectiveness = effectiveness_from(&history, &logs, &journal.alarms()?, Utc::now().date_naive());
+    let best = rank_techniques(&effectiveness).into_iter()
+        .find(|(_, stats)| stats.attempts > 0);
+    match best {
+        Some((technique, stats)) => println!("  Best technique: {} ({:.1}% success over {} sessions)",
+            paint(technique, Style::Value), stats.success_rate, stats.attempts),
+        None => println!("  No technique practice recorded"),
+    }
+    println!("  Practice sessions: {}", history.len());
+    
+    println!("\n{}", paint("Habits", Style::Section));
+    println!("  Reality checks: {}", logs.iter().map(|l| l.reality_checks).sum::<u32>());
+    let qualities: Vec<f32> = logs.iter().filter_map(DailyLog::sleep_quality).collect();
+    if qualities.is_empty() {
+        println!("  No sleep data recorded");
     } else {
-        match cli.command {
-            Commands::Dream(dream_cmd) => match dream_cmd.action {
-                DreamActions::Add => add_dream()?,
-                DreamActions::List => list_dreams()?,
-                DreamActions::View { id } => view_dream(id)?,
-                DreamActions::Search { keyword } => search_dreams(&keyword)?,
-            },
-            Commands::Train(train_cmd) => match train_cmd.technique {
-                Technique::Mild => practice_technique("MILD")?,
-                Technique::Wbtb => practice_technique("WBTB")?,
-                Technique::Fild => practice_technique("FILD")?,
-                Technique::Rc => practice_technique("RC")?,
-            },
-            Commands::Stats => show_statistics()?,
-            Commands::RealityCheck => reality_check()?,
-            Commands::Daily => daily_entry()?,
-            Commands::Alarm(alarm_cmd) => match alarm_cmd.action {
-                AlarmActions::List => list_alarms()?,
-                AlarmActions::Cancel { id } => cancel_alarm(id)?,
-                _ => unreachable!(),
-            },
-            Commands::Analyze => calculate_technique_effectiveness().map(|_| ())?,
-            Commands::Report => generate_effectiveness_report()?,
+        let durations: Vec<i64> = logs.iter().filter_map(DailyLog::total_sleep_minutes).collect();
+        let avg_quality = qualities.iter().sum::<f32>() / qualities.len() as f32;
+        println!("  Average sleep quality: {:.1}/{}", avg_quality, journal.config()?.quality_scale);
+        if !durations.is_empty() {
+            println!("  Average sleep duration: {:.1} hours",
+                durations.iter().sum::<i64>() as f32 / durations.len() as f32 / 60.0);
         }
     }
+    decor!();
+    
+    Ok(())
+}
 
-    if should_wait {
-        println!("Alarm is active. Press 'q' to quit or wait for alarm...");
-        loop {
-            if event::poll(Duration::from_millis(100))? {
-                if let Event::Key(key_event) = event::read()? {
-                    if key_event.code == KeyCode::Char('q') {
-                        println!("Exiting program. Alarm will not trigger.");
-                        break;
-                    }
-                }
-            }
-            
-            if ALARM_ACTIVE.load(Ordering::Relaxed) {
-                println!("Alarm triggered. Waiting for awake period completion...");
-                
-                while ALARM_ACTIVE.load(Ordering::Relaxed) {
-                    thread::sleep(Duration::from_secs(1));
-                }
-                
-                println!("Awake period completed. Program will now exit.");
-                break;
-            }
+fn export_techniques(journal: &Journal, out: Option<&str>) -> anyhow::Result<()> {
+    let history = journal.technique_history()?;
+    let csv = technique_history_csv(&history);
+
+    match out {
+        Some(path) => {
+            fs::write(path, csv)?;
+            println!("Exported {} practice sessions to {}", history.len(), path);
         }
+        None => print!("{}", csv),
     }
 
     Ok(())
 }
 
+fn export_html(journal: &Journal, out: &str) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    let html = journal_html(&dreams, &journal.technique_effectiveness()?, Local::now().date_naive());
+    fs::write(out, html)?;
+    println!("Exported {} dreams to {}", dreams.len(), out);
+    Ok(())
+}
+
+/// Puts the terminal back to normal: raw mode off, screen flash and colors reset.
+fn restore_terminal() {
+    let _ = terminal::disable_raw_mode();
+    if io::stdout().is_terminal() {
+        print!("\x1b[?5l\x1b[0m");
+        let _ = io::stdout().flush();
+    }
+}
 
-fn practice_technique(technique: &str) -> anyhow::Result<()> {
-    let mut techniques = load_techniques()?;
-    let tech = techniques.get_mut(technique)
-        .ok_or_else(|| anyhow::anyhow!("Technique not found"))?;
+/// Restores the terminal when the user hits Ctrl+C or the program panics mid-flow.
+fn install_terminal_cleanup() {
+    let _ = ctrlc::set_handler(|| {
+        restore_terminal();
+        decor!();
+        std::process::exit(130);
+    });
     
-    println!("\n--- Practicing {} ---", tech.name);
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore_terminal();
+        default_hook(info);
+    }));
+}
+
+/// Leaves raw mode when dropped, so an early return or panic doesn't leave the terminal broken.
+struct RawModeGuard;
+
+impl RawModeGuard {
+    fn enable() -> anyhow::Result<RawModeGuard> {
+        terminal::enable_raw_mode()?;
+        Ok(RawModeGuard)
+    }
+}
+
+impl Drop for RawModeGuard {
+    fn drop(&mut self) {
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Shows a live elapsed-time counter until any key is pressed, then returns the elapsed time.
+/// With a `target`, counts down instead and also stops once it runs out, beeping if `beep` is set.
+fn run_practice_timer(target: Option<Duration>, beep: bool) -> anyhow::Result<Duration> {
+    if noninteractive() {
+        let target = target.ok_or_else(|| JournalError::InvalidInput(format!(
+            "{} is set, so an open-ended practice can't be ended with a key; pass --duration", NONINTERACTIVE_VAR)))?;
+        thread::sleep(target);
+        if beep {
+            play_short_beep();
+        }
+        return Ok(target);
+    }
+    let start = Instant::now();
+    let _raw = RawModeGuard::enable()?;
+    let mut shown = None;
+    
+    loop {
+        let elapsed = start.elapsed().as_secs();
+        if shown != Some(elapsed) {
+            match target {
+                Some(target) => {
+                    let remaining = target.as_secs().saturating_sub(elapsed);
+                    print!("\r  Remaining: {:02}:{:02}  (press any key to end early)", remaining / 60, remaining % 60);
+                }
+                None => print!("\r  Elapsed: {:02}:{:02}  (press any key to finish)", elapsed / 60, elapsed % 60),
+            }
+            io::stdout().flush()?;
+            shown = Some(elapsed);
+        }
+        
+        if target.is_some_and(|target| start.elapsed() >= target) {
+            if beep {
+                play_short_beep();
+            }
+            break;
+        }
+        
+        if event::poll(Duration::from_millis(250))?
+            && let Event::Key(key_event) = event::read()?
+            && key_event.kind == KeyEventKind::Press
+            && key_event.code != KeyCode::Null {
+            break;
+        }
+    }
+    
+    print!("\r\n");
+    Ok(start.elapsed())
+}
+
+const EXIT_FAILURE: u8 = 1;
+const EXIT_NOT_FOUND: u8 = 2;
+const EXIT_INVALID_INPUT: u8 = 3;
+const EXIT_CORRUPT: u8 = 4;
+
+const EXIT_CODES_HELP: &str = "Exit codes:
+  0  success
+  1  any other failure, e.g. a file that can't be written
+  2  not found: no such dream, alarm, technique or preset
+  3  invalid input: bad arguments, times, dates or answers
+  4  data corruption: a data file can't be read, or `verify` found problems";
+
+/// Maps an error to the exit code scripts can branch on, see `EXIT_CODES_HELP`.
+fn exit_code(error: &anyhow::Error) -> u8 {
+    for cause in error.chain() {
+        if let Some(error) = cause.downcast_ref::<JournalError>() {
+            return match error {
+                JournalError::NotFound(_) => EXIT_NOT_FOUND,
+                JournalError::InvalidInput(_) => EXIT_INVALID_INPUT,
+                JournalError::Corrupt(_) => EXIT_CORRUPT,
+            };
+        }
+        if cause.is::<std::num::ParseIntError>() || cause.is::<std::num::ParseFloatError>() || cause.is::<chrono::ParseError>() {
+            return EXIT_INVALID_INPUT;
+        }
+        if cause.is::<serde_json::Error>() {
+            return EXIT_CORRUPT;
+        }
+    }
+    EXIT_FAILURE
+}
+
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(error) => {
+            let _ = error.print();
+            return if error.use_stderr() { ExitCode::from(EXIT_INVALID_INPUT) } else { ExitCode::SUCCESS };
+        }
+    };
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {:#}", error);
+            ExitCode::from(exit_code(&error))
+        }
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    install_terminal_cleanup();
+    QUIET.store(cli.quiet, Ordering::Relaxed);
+    let rng = &mut match cli.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_entropy(),
+    };
+    let journal = &Journal::open_verbose(".", cli.verbose)?;
+    init_color(cli.no_color, journal.config().map(|c| c.color_theme).unwrap_or_default());
+    let mut should_wait = false;
+    let Some(command) = cli.command else {
+        return run_menu(journal, rng);
+    };
+    let remind = !matches!(command, Commands::Daily(_) | Commands::Status)
+        && journal.config().is_ok_and(|c| c.daily_reminder_everywhere);
+
+    if let Commands::Alarm(AlarmCommands { action: AlarmActions::Set { bedtime, wake_time, awake_minutes, preset, silent, force } }) = &command {
+        let (wake_time, awake_minutes) = match preset {
+            Some(name) => {
+                let preset = journal.config()?.alarm_preset(name)
+                    .ok_or_else(|| JournalError::NotFound(format!("Unknown alarm preset '{}'", name)))?;
+                let wake_time = time_after(bedtime, preset.sleep_minutes)?;
+                println!("Preset {}: wake at {} after {}h {}m of sleep", name, wake_time,
+                    preset.sleep_minutes / 60, preset.sleep_minutes % 60);
+                (wake_time, awake_minutes.unwrap_or(preset.awake_minutes))
+            }
+            None => (wake_time.clone().unwrap_or_default(), awake_minutes.unwrap_or(DEFAULT_AWAKE_MINUTES)),
+        };
+        should_wait = set_wbtb_alarm(journal, bedtime, &wake_time, awake_minutes, *silent, *force)?;
+    } else {
+        match command {
+            Commands::Dream(dream_cmd) => match dream_cmd.action {
+                DreamActions::Add { title, tags, non_interactive, editor } => {
+                    add_dream(journal, title.as_deref(), tags.as_deref(), non_interactive, editor)?
+                }
+                DreamActions::List { limit, page, per_page, sort, filter, format } => {
+                    list_dreams(journal, limit, page, per_page, sort, &filter, format)?
+                }
+                DreamActions::View { id } => view_dream(journal, id)?,
+                DreamActions::Open { id } => open_dream(journal, id)?,
+                DreamActions::Random { filter, count } => random_dreams(journal, &filter, count, rng)?,
+                DreamActions::Search { keyword, regex } => search_dreams(journal, &keyword, regex)?,
+                DreamActions::Link { id, other_id } => link_dreams(journal, id, other_id)?,
+                DreamActions::Related { id, limit } => show_related_dreams(journal, id, limit)?,
+                DreamActions::Append { id, editor } => append_to_dream(journal, id, editor)?,
+                DreamActions::Lucid { id, lucid } => {
+                    let was_lucid = journal.dreams()?.iter().any(|d| d.id == id && d.lucid == Some(true));
+                    let dream = journal.set_dream_lucid(id, lucid)?;
+                    println!("Dream #{} \"{}\" is now {}.", dream.id, dream.title, if lucid { "lucid" } else { "not lucid" });
+                    if lucid && !was_lucid {
+                        print_lucid_milestone(journal, &dream)?;
+                    }
+                    if lucid && let Some(alarm_id) = journal.infer_alarm_success(id)? {
+                        println!("WBTB alarm #{} from that night is now marked successful.", alarm_id);
+                    }
+                }
+                DreamActions::Star { id } => {
+                    let dream = journal.set_dream_starred(id, true)?;
+                    println!("★ Dream #{} \"{}\" is starred.", dream.id, dream.title);
+                }
+                DreamActions::Unstar { id } => {
+                    let dream = journal.set_dream_starred(id, false)?;
+                    println!("Dream #{} \"{}\" is no longer starred.", dream.id, dream.title);
+                }
+                DreamActions::Delete { id, yes } => delete_dream(journal, id, yes)?,
+                DreamActions::Undo => undo(journal)?,
+                DreamActions::MigrateStorage { to } => migrate_storage(journal, to)?,
+                DreamActions::Import { format, path } => import_dreams(journal, format, &path)?,
+                DreamActions::Retag { from, to, dry_run } => retag_dreams(journal, &from, &to, dry_run)?,
+                DreamActions::Prune { min_words, dry_run, yes, force } => prune_dreams(journal, min_words, dry_run, yes, force)?,
+            },
+            Commands::Train(train_cmd) => match train_cmd.technique {
+                Technique::Mild => practice_technique(journal, "MILD", None, false)?,
+                Technique::Wbtb => practice_technique(journal, "WBTB", None, false)?,
+                Technique::Fild => practice_technique(journal, "FILD", None, false)?,
+                Technique::Rc => practice_technique(journal, "RC", None, false)?,
+                Technique::Practice { name, duration, beep } => practice_technique(journal, &name, duration, beep)?,
+                Technique::List => list_techniques(journal)?,
+                Technique::View { name } => view_technique(journal, &name)?,
+                Technique::Status { days } => technique_status(journal, days)?,
+            },
+            Commands::Stats { action: Some(StatsActions::Compare { first, second }), .. } => {
+                compare_periods(journal, &first, &second)?
+            }
+            Commands::Stats { action: None, rebuild } => {
+                if rebuild {
+                    journal.rebuild_statistics()?;
+                    println!("Statistics rebuilt from scratch.");
+                }
+                show_statistics(journal)?
+            }
+            Commands::RealityCheck { category, every: None, log, anchor, count } => {
+                reality_check(journal, category.as_deref(), log, anchor.as_deref(), count, rng)?
+            }
+            Commands::RealityCheck { category, every: Some(minutes), log, count, .. } => watch_reality_checks(journal, category.as_deref(), minutes, log, count, rng)?,
+            Commands::Daily(daily_cmd) => match daily_cmd.action {
+                None => daily_entry(journal, daily_cmd.notes, daily_cmd.dry_run)?,
+                Some(DailyActions::Search { keyword }) => daily_search(journal, &keyword)?,
+                Some(DailyActions::View { date }) => view_daily_log(journal, date.as_deref())?,
+                Some(DailyActions::Repair) => repair_daily_logs(journal)?,
+            },
+            Commands::Alarm(alarm_cmd) => match alarm_cmd.action {
+                AlarmActions::List => list_alarms(journal)?,
+                AlarmActions::Cancel { id, all, wake_time, yes } => cancel_alarm(journal, id, all, wake_time.as_deref(), yes)?,
+                AlarmActions::Preview { wake_time } => preview_alarm(journal, &wake_time)?,
+                AlarmActions::Log { lines, follow } => alarm_log(journal, lines, follow)?,
+                _ => unreachable!(),
+            },
+            Commands::SleepCycles { bedtime, wake, cycle_minutes } => {
+                let format = journal.config()?.time_format;
+                let cycles = sleep_cycles(&bedtime, &wake, cycle_minutes)
+                    .ok_or_else(|| JournalError::InvalidInput(format!("Invalid bedtime '{}', wake time '{}' or cycle length", bedtime, wake)))?;
+                print_sleep_cycles(&cycles, format);
+            }
+            Commands::Analyze { since_last } => analyze(journal, since_last)?,
+            Commands::Report { period, recent_days, format } => match period {
+                None => generate_effectiveness_report(journal, recent_days, format)?,
+                Some(ReportPeriod::Weekly { since_last }) => {
+                    let marker = if since_last { since_last_marker(journal, "weekly")? } else { None };
+                    generate_weekly_report(journal, marker.as_ref())?;
+                    if since_last {
+                        journal.mark_report("weekly")?;
+                    }
+                }
+                Some(ReportPeriod::Yearly { year }) => yearly_report(journal, year)?,
+                Some(ReportPeriod::RealityChecks) => reality_check_report(journal)?,
+                Some(ReportPeriod::Anchors) => anchor_report(journal)?,
+                Some(ReportPeriod::Control { sessions }) => control_report(journal, sessions)?,
+            },
+            Commands::Status => show_status(journal)?,
+            Commands::Verify => verify(journal)?,
+            Commands::Config(config_cmd) => match config_cmd.action {
+                ConfigActions::Show => show_config(journal)?,
+                ConfigActions::Set { key, value } => set_config(journal, &key, &value)?,
+            },
+            Commands::Export(export_cmd) => match export_cmd.action {
+                ExportActions::Techniques { out } => export_techniques(journal, out.as_deref())?,
+                ExportActions::Html { out } => export_html(journal, &out)?,
+            },
+        }
+    }
+
+    if remind {
+        print_daily_reminder(journal)?;
+    }
+
+    if should_wait {
+        println!("Alarm is active. Press 'q' to quit or wait for alarm...");
+        loop {
+            if quit_pressed(Duration::from_millis(100))? {
+                println!("Exiting program. Alarm will not trigger.");
+                break;
+            }
+            
+            if ALARM_CANCELED.load(Ordering::Relaxed) {
+                println!("Alarm was canceled. Program will now exit.");
+                break;
+            }
+            
+            if ALARM_ACTIVE.load(Ordering::Relaxed) {
+                println!("Alarm triggered. Waiting for awake period completion...");
+                
+                while ALARM_ACTIVE.load(Ordering::Relaxed) {
+                    thread::sleep(Duration::from_secs(1));
+                }
+                
+                println!("Awake period completed. Program will now exit.");
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+const MENU_ITEMS: [&str; 11] = [
+    "Daily entry (sleep + dreams)",
+    "Add a dream",
+    "List dreams",
+    "Search dreams",
+    "View a dream",
+    "Statistics",
+    "Practice a technique",
+    "Reality check",
+    "Technique effectiveness report",
+    "WBTB alarms",
+    "Status",
+];
+
+/// Numbered menu over the everyday commands, for running without a subcommand.
+/// A failing choice prints its error and returns to the menu.
+fn run_menu(journal: &Journal, rng: &mut StdRng) -> anyhow::Result<()> {
+    if noninteractive() {
+        anyhow::bail!(JournalError::InvalidInput(format!("{} is set, so there's no menu; pass a subcommand", NONINTERACTIVE_VAR)));
+    }
+    let ask = |label: &str| prompt("MENU", label);
+    
+    loop {
+        decor!("\n{}", paint("LUCID DREAMER", Style::Heading));
+        for (i, item) in MENU_ITEMS.iter().enumerate() {
+            println!("{:>3}. {}", i + 1, item);
+        }
+        println!("  q. Quit");
+        print!("Choose an option: ");
+        io::stdout().flush()?;
+        let mut choice = String::new();
+        if io::stdin().read_line(&mut choice)? == 0 {
+            println!();
+            return Ok(());
+        }
+        
+        let result = match choice.trim() {
+            "1" => daily_entry(journal, NotesInput::Line, false),
+            "2" => add_dream(journal, None, None, false, false),
+            "3" => list_dreams(journal, None, None, DEFAULT_PER_PAGE, DreamSort::Id, &DreamFilter::default(), ListFormat::Table),
+            "4" => ask("Search for: ").and_then(|keyword| search_dreams(journal, &keyword, false)),
+            "5" => ask("Dream ID: ").and_then(|id| view_dream(journal, id.parse()?)),
+            "6" => show_statistics(journal),
+            "7" => ask("Technique (MILD, WBTB, FILD, RC, ...): ").and_then(|name| practice_technique(journal, &name, None, false)),
+            "8" => reality_check(journal, None, false, None, 1, rng),
+            "9" => generate_effectiveness_report(journal, None, None),
+            "10" => list_alarms(journal),
+            "11" => show_status(journal),
+            "q" | "Q" => return Ok(()),
+            "" => Ok(()),
+            other => Err(JournalError::InvalidInput(format!("Unknown menu option '{}'", other)).into()),
+        };
+        if let Err(error) = result {
+            eprintln!("Error: {:#}", error);
+        }
+    }
+}
+
+fn technique_status(journal: &Journal, days: Option<u32>) -> anyhow::Result<()> {
+    let due_days = match days {
+        Some(days) => days,
+        None => journal.config()?.technique_due_days,
+    };
+    let techniques = journal.techniques()?;
+    let rotation = technique_rotation(&techniques, &journal.technique_history()?, Utc::now().date_naive());
+
+    decor!("\n{}", paint("=== Technique Rotation ===", Style::Heading));
+    for (key, days) in rotation {
+        let name = &techniques[&key].name;
+        match days {
+            None => println!("{} {} - never practiced", paint("[DUE]", Style::Bad), name),
+            Some(days) if days >= i64::from(due_days) => {
+                println!("{} {} - {} days ago", paint("[due]", Style::Bad), name, days);
+            }
+            Some(days) => println!("      {} - {} days ago", name, days),
+        }
+    }
+    Ok(())
+}
+
+fn list_techniques(journal: &Journal) -> anyhow::Result<()> {
+    let techniques = journal.techniques()?;
+    let mut keys: Vec<_> = techniques.keys().collect();
+    keys.sort();
+
+    decor!("\n{}", paint("=== Techniques ===", Style::Heading));
+    for key in keys {
+        let tech = &techniques[key];
+        println!("{} - {}", paint(key, Style::Accent), tech.name);
+        println!("      {}", tech.description);
+    }
+    decor!("\nRun `train view <name>` for the steps, or `train practice <name>` to start a session.");
+    Ok(())
+}
+
+fn view_technique(journal: &Journal, name: &str) -> anyhow::Result<()> {
+    let techniques = journal.techniques()?;
+    let tech = &techniques[&resolve_technique(techniques.keys(), name)?];
+
+    println!("\n--- {} ---", tech.name);
+    print_technique_steps(tech);
+    match tech.last_practiced {
+        Some(date) => println!("\nLast practiced: {}", date),
+        None => println!("\nNot practiced yet"),
+    }
+    Ok(())
+}
+
+fn print_technique_steps(tech: &TechniqueData) {
     println!("{}\n", tech.description);
     println!("Steps:");
     for (i, step) in tech.steps.iter().enumerate() {
         println!("{}. {}", i + 1, step);
     }
+}
+
+fn practice_technique(journal: &Journal, technique: &str, minutes: Option<u64>, beep: bool) -> anyhow::Result<()> {
+    let mut techniques = journal.techniques()?;
+    let technique = &resolve_technique(techniques.keys(), technique)?;
+    let tech = techniques.get_mut(technique).unwrap();
     
-    let start_time = SystemTime::now();
-    tech.last_practiced = Some(Utc::now().format("%Y-%m-%d").to_string());
-    save_techniques(&techniques)?;
+    println!("\n--- Practicing {} ---", tech.name);
+    print_technique_steps(tech);
     
-    println!("\nPractice started at {}", Utc::now().format("%H:%M"));
-    println!("Press any key to complete practice...");
-    wait_for_keypress()?;
+    tech.last_practiced = Some(Utc::now().date_naive());
+    journal.save_techniques(&techniques)?;
     
-    let duration = start_time.elapsed().unwrap().as_secs() / 60;
+    match minutes {
+        Some(minutes) => println!("\nPractice started at {} for {} minutes", Utc::now().format("%H:%M"), minutes),
+        None => println!("\nPractice started at {}", Utc::now().format("%H:%M")),
+    }
+    let duration = run_practice_timer(minutes.map(|m| Duration::from_secs(m * 60)), beep)?.as_secs() / 60;
     println!("\nPractice duration: {} minutes", duration);
     
+    let custom: Vec<_> = journal.config()?.custom_outcomes.into_iter()
+        .filter(|outcome| outcome.applies_to(technique))
+        .collect();
     println!("Select outcome:");
     println!("1. Failed (no lucidity)");
     println!("2. Partial lucidity (brief awareness)");
     println!("3. Full lucidity (complete control)");
+    for (i, outcome) in custom.iter().enumerate() {
+        println!("{}. {}{}", i + 4, outcome.label, if outcome.success { " (counts as a success)" } else { "" });
+    }
     
-    let mut choice = String::new();
-    io::stdin().read_line(&mut choice)?;
+    let choice = prompt("OUTCOME", &format!("Outcome (1-{}): ", custom.len() + 3))?;
     let outcome = match choice.trim() {
         "1" => TechniqueOutcome::Failed,
         "2" => TechniqueOutcome::PartialLucid,
         "3" => {
-            print!("Control level (1-5): ");
-            io::stdout().flush()?;
-            let mut control = String::new();
-            io::stdin().read_line(&mut control)?;
+            let control = prompt("CONTROL", "Control level (1-5): ")?;
             let control_level = control.trim().parse().unwrap_or(3).clamp(1, 5);
             TechniqueOutcome::FullLucid { control_level }
         }
-        _ => TechniqueOutcome::Unattempted,
+        choice => match choice.parse::<usize>().ok().and_then(|n| n.checked_sub(4)).and_then(|i| custom.get(i)) {
+            Some(outcome) => TechniqueOutcome::Custom { label: outcome.label.clone(), success: outcome.success },
+            None => TechniqueOutcome::Unattempted,
+        },
     };
     
-    record_technique_practice(technique, outcome, duration as u32)?;
+    journal.record_technique_practice(technique, outcome, duration as u32)?;
     
     println!("\n✅ Practice recorded! Technique effectiveness updated.");
     Ok(())
 }
 
-fn add_dream() -> anyhow::Result<()> {
-    let mut dreams = load_dreams()?;
-    let id = dreams.last().map_or(1, |d| d.id + 1);
+/// Adds a dream, asking for whatever `title` and `tags` don't provide. With `non_interactive`,
+/// nothing is asked: the content is read from stdin, and a duplicate is an error rather than a question.
+fn add_dream(journal: &Journal, title: Option<&str>, tags: Option<&str>, non_interactive: bool, editor: bool) -> anyhow::Result<()> {
+    let title = match title {
+        Some(title) => title.to_string(),
+        None => prompt("TITLE", "Dream title: ")?,
+    };
     
-    print!("Dream title: ");
-    io::stdout().flush()?;
-    let mut title = String::new();
-    io::stdin().read_line(&mut title)?;
+    let content = if non_interactive {
+        let mut content = String::new();
+        io::stdin().read_to_string(&mut content)?;
+        content
+    } else {
+        prompt_dream_content(journal, editor)?
+    };
+    
+    if title.trim().is_empty() || content.trim().is_empty() {
+        if non_interactive {
+            return Err(JournalError::InvalidInput("Dream not saved: title and content can't be empty".to_string()).into());
+        }
+        println!("Dream not saved: title and content can't be empty.");
+        return Ok(());
+    }
     
-    println!("Dream content (Ctrl+D when finished):");
-    let mut content = String::new();
-    io::stdin().read_to_string(&mut content)?;
+    let date = Utc::now().date_naive();
+    if non_interactive {
+        if let Some(existing) = find_duplicate(&journal.dreams()?, date, &title, &content) {
+            return Err(JournalError::InvalidInput(format!(
+                "Dream not saved: looks like a duplicate of dream #{} \"{}\"", existing.id, existing.title)).into());
+        }
+    } else if !confirm_if_duplicate(journal, date, &title, &content)? {
+        println!("Dream not saved.");
+        return Ok(());
+    }
     
-    print!("Tags (comma separated): ");
-    io::stdout().flush()?;
-    let mut tags_input = String::new();
-    io::stdin().read_line(&mut tags_input)?;
-    let tags: Vec<String> = tags_input.split(',')
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
-        .collect();
+    let tags = match tags {
+        Some(tags) => split_tags(tags),
+        None if non_interactive => Vec::new(),
+        None => prompt_tags(journal, &format!("{} {}", title, content), true)?,
+    };
+    let vividness = if non_interactive { None } else { prompt_vividness()? };
+    let setting = if non_interactive { None } else { prompt_setting()? };
     
-    let new_dream = Dream {
-        id,
-        date: Utc::now().format("%Y-%m-%d").to_string(),
+    let new_dream = journal.add_dream(Dream {
+        id: 0,
+        date,
         title: title.trim().to_string(),
         content: content.trim().to_string(),
         tags,
         lucid: None,
         dream_sign: None,
-    };
+        vividness,
+        setting,
+        ..Default::default()
+    })?;
+    println!("Dream #{} added successfully!", new_dream.id);
     
-    dreams.push(new_dream);
-    save_dreams(&dreams)?;
-    println!("Dream #{} added successfully!", id);
+    Ok(())
+}
 
-    update_statistics()?;
+/// Asks before saving a dream that looks like one already recorded for `date`; true means go ahead.
+fn confirm_if_duplicate(journal: &Journal, date: NaiveDate, title: &str, content: &str) -> anyhow::Result<bool> {
+    let dreams = journal.dreams()?;
+    let Some(existing) = find_duplicate(&dreams, date, title, content) else {
+        return Ok(true);
+    };
     
+    let answer = prompt("CONFIRM", &format!("Looks like a duplicate of dream #{} \"{}\" — add anyway? (y/N): ", existing.id, existing.title))?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+fn import_dreams(journal: &Journal, format: ImportFormat, path: &str) -> anyhow::Result<()> {
+    let text = fs::read_to_string(path)?;
+    let (entries, skipped) = match format {
+        ImportFormat::Diary => parse_diary(&text),
+    };
+
+    let dreams = entries.into_iter()
+        .map(|(date, title, content)| Dream {
+            date,
+            title,
+            content,
+            ..Default::default()
+        })
+        .collect();
+    let imported = journal.add_dreams(dreams)?;
+
+    println!("Imported {} dreams, skipped {} sections.", imported.len(), skipped);
+    Ok(())
+}
+
+fn migrate_storage(journal: &Journal, to: StorageLayout) -> anyhow::Result<()> {
+    let (storage, place) = match to {
+        StorageLayout::Json => (DreamStorage::Json, "dreams.json"),
+        StorageLayout::Files => (DreamStorage::Files, "dreams/"),
+    };
+    let moved = journal.migrate_dream_storage(storage)?;
+    println!("Moved {} dreams to {}.", moved, place);
     Ok(())
 }
 
-fn list_dreams() -> anyhow::Result<()> {
-    let dreams = load_dreams()?;
+fn list_dreams(
+    journal: &Journal,
+    limit: Option<usize>,
+    page: Option<usize>,
+    per_page: usize,
+    sort: DreamSort,
+    filter: &DreamFilter,
+    format: ListFormat,
+) -> anyhow::Result<()> {
+    let mut dreams = journal.dreams()?;
     if dreams.is_empty() {
         println!("No dreams recorded yet.");
         return Ok(());
     }
+    dreams.retain(|d| filter.matches(d));
+    if dreams.is_empty() {
+        println!("No dreams match those filters.");
+        return Ok(());
+    }
     
-    println!("{:<5} {:<12} {:<30} {:<20}", "ID", "Date", "Title", "Tags");
+    match sort {
+        DreamSort::DateAsc => dreams.sort_by_key(|d| (d.date, d.id)),
+        DreamSort::DateDesc => dreams.sort_by_key(|d| std::cmp::Reverse((d.date, d.id))),
+        DreamSort::Id => dreams.sort_by_key(|d| d.id),
+        DreamSort::Title => dreams.sort_by_key(|d| d.title.to_lowercase()),
+    }
+    dreams.truncate(limit.unwrap_or(dreams.len()));
+    
+    let total = dreams.len();
+    let per_page = per_page.max(1);
+    let pages = total.div_ceil(per_page);
+    if let Some(page) = page {
+        if page == 0 || page > pages {
+            anyhow::bail!(JournalError::InvalidInput(format!("Page {} is out of range (1-{})", page, pages)));
+        }
+        dreams = dreams.into_iter().skip((page - 1) * per_page).take(per_page).collect();
+    }
+    
+    let mut out = String::new();
+    if let ListFormat::Md = format {
+        let cell = |text: &str| text.replace('|', "\\|").replace('\n', " ");
+        writeln!(out, "| ID | Date | Title | Tags |")?;
+        writeln!(out, "|---:|------|-------|------|")?;
+        for dream in dreams {
+            writeln!(out, "| {} | {} | {} | {} |", dream.id, dream.date, cell(&starred_title(&dream)), cell(&dream.tags.join(", ")))?;
+        }
+        print!("{}", out);
+        return Ok(());
+    }
+    
+    writeln!(out, "{:<5} {:<12} {:<30} {:<20}", "ID", "Date", "Title", "Tags")?;
     for dream in dreams {
         let tags = dream.tags.join(", ");
-        println!("{:<5} {:<12} {:<30} {:<20}", dream.id, dream.date, dream.title, tags);
+        writeln!(out, "{:<5} {:<12} {:<30} {:<20}", dream.id, dream.date.to_string(), starred_title(&dream), tags)?;
+    }
+    if let Some(page) = page {
+        writeln!(out, "\nPage {} of {} ({} dreams)", page, pages, total)?;
     }
     
-    Ok(())
+    page_output(&out)
 }
 
-fn view_dream(id: u32) -> anyhow::Result<()> {
-    let dreams = load_dreams()?;
-    if let Some(dream) = dreams.iter().find(|d| d.id == id) {
-        println!("\n--- Dream #{} ---", dream.id);
-        println!("Date: {}", dream.date);
-        println!("Title: {}", dream.title);
-        println!("Tags: {}", dream.tags.join(", "));
-        println!("\nContent:\n{}\n", dream.content);
-        
-        if let Some(sign) = &dream.dream_sign {
-            println!("Dream sign: {}", sign);
+fn starred_title(dream: &Dream) -> String {
+    if dream.starred { format!("★ {}", dream.title) } else { dream.title.clone() }
+}
+
+/// Prints `text`, through `$PAGER` (or `less`) when stdout is a terminal it wouldn't fit on.
+fn page_output(text: &str) -> anyhow::Result<()> {
+    let fits = terminal::size().is_ok_and(|(_, rows)| text.lines().count() < rows as usize);
+    if !io::stdout().is_terminal() || fits {
+        print!("{}", text);
+        return Ok(());
+    }
+    
+    let pager = std::env::var("PAGER").unwrap_or_else(|_| "less -R".to_string());
+    let mut parts = pager.split_whitespace();
+    let spawned = parts.next().map(|program| {
+        Command::new(program).args(parts).stdin(Stdio::piped()).spawn()
+    });
+    match spawned {
+        Some(Ok(mut child)) => {
+            if let Some(mut stdin) = child.stdin.take() {
+                // The pager closing early (e.g. `q` in less) is not an error.
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            child.wait()?;
         }
-        if let Some(lucid) = dream.lucid {
-            println!("Lucid: {}", lucid);
+        _ => print!("{}", text),
+    }
+    Ok(())
+}
+
+fn view_dream(journal: &Journal, id: u32) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    let dream = dreams.iter().find(|d| d.id == id)
+        .ok_or_else(|| JournalError::NotFound(format!("Dream #{} not found", id)))?;
+    print_dream(dream, &dreams);
+    
+    Ok(())
+}
+
+fn open_dream(journal: &Journal, id: u32) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    let dream = dreams.iter().find(|d| d.id == id)
+        .ok_or_else(|| JournalError::NotFound(format!("Dream #{} not found", id)))?;
+    
+    let mut markdown = format!("# {}\n\n*{}*", dream.title, dream.date);
+    if !dream.tags.is_empty() {
+        markdown.push_str(&format!(" · {}", dream.tags.join(", ")));
+    }
+    markdown.push_str(&format!("\n\n{}\n", dream.content));
+    
+    let path = write_temp_file(&format!("dream-{}.md", dream.id), &markdown)?;
+    open_with_default_app(&path)?;
+    println!("Opened dream #{} ({})", dream.id, path.display());
+    
+    if !journal.config()?.keep_opened_files {
+        // Give the viewer time to read the file before it goes away.
+        thread::sleep(Duration::from_secs(OPEN_CLEANUP_SECS));
+        let _ = fs::remove_file(&path);
+    }
+    Ok(())
+}
+
+fn append_to_dream(journal: &Journal, id: u32, editor: bool) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    let dream = dreams.iter().find(|d| d.id == id)
+        .ok_or_else(|| JournalError::NotFound(format!("Dream #{} not found", id)))?;
+    
+    let addition = if editor {
+        let marker = "--- Write what else you remember below this line ---";
+        let edited = edit_text(&format!("dream-{}-append.md", dream.id),
+            &format!("{}\n\n{}\n", dream.content.trim_end(), marker))?;
+        match edited.split_once(marker) {
+            Some((_, addition)) => addition.to_string(),
+            None => anyhow::bail!("The marker line was removed; dream left unchanged"),
         }
     } else {
-        println!("Dream #{} not found.", id);
-    }
+        println!("--- Dream #{}: {} ---\n{}\n", dream.id, dream.title, dream.content);
+        prompt_text("APPEND", "What else do you remember?")?
+    };
     
+    if addition.trim().is_empty() {
+        println!("Nothing added.");
+        return Ok(());
+    }
+    journal.append_to_dream(id, &addition)?;
+    println!("Added to dream #{}.", id);
     Ok(())
 }
 
-fn search_dreams(keyword: &str) -> anyhow::Result<()> {
-    let dreams = load_dreams()?;
-    let keyword = keyword.to_lowercase();
-    let mut found = false;
+/// Opens `contents` in $EDITOR (vi if unset) as the temporary file `name`, returning the edited text.
+fn edit_text(name: &str, contents: &str) -> anyhow::Result<String> {
+    let path = write_temp_file(name, contents)?;
+    let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
+    let status = Command::new(&editor).arg(&path).status();
+    let edited = fs::read_to_string(&path);
+    let _ = fs::remove_file(&path);
+    if !status.is_ok_and(|s| s.success()) {
+        anyhow::bail!("Editor '{}' didn't exit cleanly; nothing was saved", editor);
+    }
+    Ok(edited?)
+}
+
+/// Asks for a dream's content, section by section when `dream_template` is configured. With
+/// `editor`, the template is written to $EDITOR instead.
+fn prompt_dream_content(journal: &Journal, editor: bool) -> anyhow::Result<String> {
+    let sections = journal.config()?.dream_template;
+    if editor {
+        let edited = edit_text("dream-new.md", &template_buffer(&sections))?;
+        return Ok(fill_template(&parse_template(&edited, &sections)));
+    }
+    if sections.is_empty() || noninteractive() {
+        return prompt_text("CONTENT", "Dream content");
+    }
     
-    for dream in dreams {
-        if dream.title.to_lowercase().contains(&keyword) || 
-           dream.content.to_lowercase().contains(&keyword) ||
-           dream.tags.iter().any(|t| t.to_lowercase().contains(&keyword)) {
-            println!("\n--- Dream #{} ---", dream.id);
-            println!("Date: {}", dream.date);
-            println!("Title: {}", dream.title);
-            println!("Tags: {}", dream.tags.join(", "));
-            found = true;
+    println!("Dream content (Enter skips a section):");
+    let mut answers = Vec::new();
+    for section in sections {
+        let text = prompt("CONTENT", &format!("  {}: ", section))?;
+        answers.push((section, text));
+    }
+    Ok(fill_template(&answers))
+}
+
+/// Writes `contents` to `name` inside a lucid-dreamer folder in the system temp directory.
+fn write_temp_file(name: &str, contents: &str) -> anyhow::Result<std::path::PathBuf> {
+    let dir = std::env::temp_dir().join("lucid-dreamer");
+    fs::create_dir_all(&dir)?;
+    let path = dir.join(name);
+    fs::write(&path, contents)?;
+    Ok(path)
+}
+
+fn open_with_default_app(path: &std::path::Path) -> anyhow::Result<()> {
+    let status = if cfg!(target_os = "windows") {
+        Command::new("cmd").args(["/C", "start", ""]).arg(path).status()
+    } else if cfg!(target_os = "macos") {
+        Command::new("open").arg(path).status()
+    } else {
+        Command::new("xdg-open").arg(path).status()
+    };
+    
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        _ => anyhow::bail!("Couldn't open {} with the default application", path.display()),
+    }
+}
+
+fn print_dream(dream: &Dream, dreams: &[Dream]) {
+    println!("\n--- Dream #{} ---", dream.id);
+    println!("Date: {}", dream.date);
+    println!("Title: {}", starred_title(dream));
+    println!("Tags: {}", dream.tags.join(", "));
+    println!("\nContent:\n{}\n", dream.content);
+    
+    if let Some(sign) = &dream.dream_sign {
+        println!("Dream sign: {}", sign);
+    }
+    if let Some(lucid) = dream.lucid {
+        println!("Lucid: {}", lucid);
+    }
+    if let Some(vividness) = dream.vividness {
+        println!("Vividness: {}/{}", vividness, VIVIDNESS_SCALE);
+    }
+    if let Some(setting) = &dream.setting {
+        println!("Setting: {}", setting);
+    }
+    
+    if !dream.links.is_empty() {
+        println!("\nLinked dreams:");
+        for link in &dream.links {
+            match dreams.iter().find(|d| d.id == *link) {
+                Some(linked) => println!("  #{} {} ({})", linked.id, linked.title, linked.date),
+                None => println!("  #{} (missing)", link),
+            }
         }
     }
+}
+
+fn random_dreams(journal: &Journal, filter: &DreamFilter, count: usize, rng: &mut StdRng) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    let pool: Vec<&Dream> = dreams.iter().filter(|d| filter.matches(d)).collect();
     
-    if !found {
-        println!("No dreams found matching '{}'", keyword);
+    if pool.is_empty() {
+        println!("No dreams match those filters.");
+        return Ok(());
+    }
+    
+    for dream in pool.choose_multiple(rng, count) {
+        print_dream(dream, &dreams);
     }
     
     Ok(())
 }
 
-fn load_dreams() -> anyhow::Result<Vec<Dream>> {
-    if !Path::new(DREAMS_FILE).exists() {
-        return Ok(Vec::new());
-    }
-    let data = fs::read_to_string(DREAMS_FILE)?;
-    Ok(serde_json::from_str(&data)?)
+fn link_dreams(journal: &Journal, id: u32, other_id: u32) -> anyhow::Result<()> {
+    journal.link_dreams(id, other_id)?;
+    println!("Linked dream #{} and dream #{}.", id, other_id);
+    Ok(())
 }
 
-fn save_dreams(dreams: &[Dream]) -> anyhow::Result<()> {
-    let data = serde_json::to_string_pretty(dreams)?;
-    fs::write(DREAMS_FILE, data)?;
+fn show_related_dreams(journal: &Journal, id: u32, limit: usize) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    let target = dreams.iter().find(|d| d.id == id)
+        .ok_or_else(|| JournalError::NotFound(format!("Dream #{} not found", id)))?;
+    
+    let related = related_dreams(target, &dreams, limit);
+    if related.is_empty() {
+        println!("No dreams share tags or words with dream #{} \"{}\".", target.id, target.title);
+        return Ok(());
+    }
+    
+    decor!("Dreams like #{} \"{}\":\n", target.id, target.title);
+    println!("{:<7} {:<5} {:<12} {:<30} {:<12} Words", "Score", "ID", "Date", "Title", "Shared tags");
+    for r in related {
+        println!("{:<7.2} {:<5} {:<12} {:<30} {:<12} {:.0}%",
+            r.score, r.dream.id, r.dream.date.to_string(), r.dream.title, r.shared_tags, r.similarity * 100.0);
+    }
     Ok(())
 }
 
-fn update_statistics() -> anyhow::Result<()> {
-    let dreams = load_dreams()?;
-    let mut stats = if Path::new(STATS_FILE).exists() {
-        let data = fs::read_to_string(STATS_FILE)?;
-        serde_json::from_str(&data)?
+fn search_dreams(journal: &Journal, keyword: &str, regex: bool) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    type Matcher = Box<dyn Fn(&Dream) -> bool>;
+    type Finder = Box<dyn Fn(&str) -> Vec<Range<usize>>>;
+    let (matches, find): (Matcher, Finder) = if regex {
+        let pattern = Regex::new(keyword)
+            .map_err(|e| JournalError::InvalidInput(format!("Invalid regular expression '{}': {}", keyword, e)))?;
+        let finder = pattern.clone();
+        (
+            Box::new(move |dream: &Dream| {
+                pattern.is_match(&dream.title)
+                    || pattern.is_match(&dream.content)
+                    || dream.tags.iter().any(|tag| pattern.is_match(tag))
+            }),
+            Box::new(move |text: &str| finder.find_iter(text).filter(|m| !m.is_empty()).map(|m| m.range()).collect()),
+        )
     } else {
-        Statistics::default()
+        let query = SearchQuery::parse(keyword);
+        if query.term_count() > 1 {
+            println!("Searching for: {}", query.describe());
+        }
+        let terms: Vec<String> = query.terms().into_iter().map(str::to_string).collect();
+        (
+            Box::new(move |dream: &Dream| query.matches(dream)),
+            Box::new(move |text: &str| find_terms(text, &terms.iter().map(String::as_str).collect::<Vec<_>>())),
+        )
     };
+    let mut found = 0;
+    let mut total = 0;
+    
+    for dream in dreams.iter().filter(|d| matches(d)) {
+        let title_hits = find(&dream.title);
+        let content_hits = find(&dream.content);
+        let tag_hits: Vec<Vec<Range<usize>>> = dream.tags.iter().map(|tag| find(tag)).collect();
+        let count = title_hits.len() + content_hits.len() + tag_hits.iter().map(Vec::len).sum::<usize>();
+        
+        println!("\n--- Dream #{} --- ({} {})", dream.id, count, if count == 1 { "match" } else { "matches" });
+        println!("Date: {}", dream.date);
+        println!("Title: {}", highlight(&dream.title, &title_hits));
+        let tags: Vec<String> = dream.tags.iter().zip(&tag_hits).map(|(tag, hits)| highlight(tag, hits)).collect();
+        println!("Tags: {}", tags.join(", "));
+        if let Some(excerpt) = highlighted_excerpt(&dream.content, &content_hits, SEARCH_SNIPPET_RADIUS) {
+            println!("  {}", excerpt);
+        }
+        found += 1;
+        total += count;
+    }
     
-    stats.total_dreams = dreams.len() as u32;
-    stats.lucid_dreams = dreams.iter()
-        .filter(|d| d.tags.contains(&"#lucid".to_string()))
-        .count() as u32;
+    if found == 0 {
+        println!("No dreams found matching '{}'", keyword.to_lowercase());
+    } else {
+        decor!();
+        println!("{} {} in {} {}.", total, if total == 1 { "match" } else { "matches" },
+            found, if found == 1 { "dream" } else { "dreams" });
+    }
     
-    for dream in &dreams {
-        for word in dream.content.split_whitespace() {
-            let word = word.to_lowercase();
-            *stats.common_words.entry(word).or_insert(0) += 1;
+    Ok(())
+}
+
+/// `text` with each of `ranges` (byte ranges, in order) highlighted.
+fn highlight(text: &str, ranges: &[Range<usize>]) -> String {
+    let mut out = String::new();
+    let mut at = 0;
+    for range in ranges {
+        out.push_str(&text[at..range.start]);
+        out.push_str(&paint(&text[range.clone()], Style::Highlight));
+        at = range.end;
+    }
+    out.push_str(&text[at..]);
+    out
+}
+
+/// Up to `radius` characters either side of the first of `ranges`, on one line, with every
+/// match inside it highlighted.
+fn highlighted_excerpt(text: &str, ranges: &[Range<usize>], radius: usize) -> Option<String> {
+    let first = ranges.first()?;
+    let start = text[..first.start].char_indices().rev().nth(radius.saturating_sub(1)).map_or(0, |(i, _)| i);
+    let end = text[first.end..].char_indices().nth(radius).map_or(text.len(), |(i, _)| first.end + i);
+    
+    let inside: Vec<Range<usize>> = ranges.iter()
+        .filter(|r| r.start >= start && r.end <= end)
+        .map(|r| r.start - start..r.end - start)
+        .collect();
+    let excerpt = highlight(&text[start..end], &inside).replace('\n', " ");
+    Some(format!("{}{}{}", if start > 0 { "…" } else { "" }, excerpt.trim(), if end < text.len() { "…" } else { "" }))
+}
+
+fn daily_search(journal: &Journal, keyword: &str) -> anyhow::Result<()> {
+    let logs = journal.daily_logs()?;
+    let matches = search_daily_logs(&logs, keyword);
+    
+    if matches.is_empty() {
+        println!("No daily logs found matching '{}'", keyword.to_lowercase());
+        return Ok(());
+    }
+    
+    for (log, hits) in matches {
+        println!("\n{}", paint(log.date, Style::Section));
+        for (field, snippet) in hits {
+            println!("  {}: {}", field, snippet);
         }
     }
     
-    for dream in &dreams {
-        let date = dream.date.clone();
-        *stats.dream_calendar.entry(date).or_insert(0) += 1;
+    Ok(())
+}
+
+fn retag_dreams(journal: &Journal, from: &str, to: &str, dry_run: bool) -> anyhow::Result<()> {
+    let affected = journal.retag_dreams(from, to, dry_run)?;
+    if affected.is_empty() {
+        println!("No dreams are tagged {}.", from);
+        return Ok(());
     }
     
-    let data = serde_json::to_string_pretty(&stats)?;
-    fs::write(STATS_FILE, data)?;
+    let ids: Vec<String> = affected.iter().map(|id| format!("#{}", id)).collect();
+    let change = if to.trim().is_empty() { format!("remove {}", from) } else { format!("retag {} as {}", from, to) };
+    if dry_run {
+        println!("Would {} on {} dreams: {}", change, affected.len(), ids.join(", "));
+    } else {
+        println!("Done: {} on {} dreams: {}", change, affected.len(), ids.join(", "));
+    }
+    Ok(())
+}
+
+fn view_daily_log(journal: &Journal, date: Option<&str>) -> anyhow::Result<()> {
+    let date = match date {
+        Some(text) => parse_date(text)
+            .ok_or_else(|| JournalError::InvalidInput(format!("Invalid date '{}', expected YYYY-MM-DD", text)))?,
+        None => Utc::now().date_naive(),
+    };
+    let logs = journal.daily_logs()?;
+    let log = logs.iter().find(|l| l.date == date)
+        .ok_or_else(|| JournalError::NotFound(format!("No daily log for {}", date)))?;
+    let config = journal.config()?;
+    
+    print_daily_summary(log, config.time_format, config.quality_scale.max(1));
+    if let Some(sleep) = log.main_sleep()
+        && let Some(cycles) = sleep_cycles(&sleep.bedtime, &sleep.wake_time, SLEEP_CYCLE_MINUTES) {
+        print_sleep_cycles(&cycles, config.time_format);
+    }
+    Ok(())
+}
+
+/// Timeline of a night at `CYCLE_SLOT_MINUTES` per character, REM as █ (lucid windows highlighted),
+/// followed by each cycle's times.
+fn print_sleep_cycles(cycles: &[SleepCycle], format: TimeFormat) {
+    let (Some(first), Some(last)) = (cycles.first(), cycles.last()) else {
+        println!("No sleep to chart.");
+        return;
+    };
+    
+    decor!("\n{}", paint("SLEEP CYCLES", Style::Heading));
+    let mut timeline = String::new();
+    for cycle in cycles {
+        let slots = (cycle.minutes() / CYCLE_SLOT_MINUTES).max(1);
+        let rem_slots = cycle.rem_start
+            .map(|rem| ((cycle.end - rem).num_minutes().rem_euclid(24 * 60) / CYCLE_SLOT_MINUTES).max(1))
+            .unwrap_or(0);
+        timeline.push_str(&"─".repeat((slots - rem_slots) as usize));
+        let rem = "█".repeat(rem_slots as usize);
+        timeline.push_str(&if cycle.lucid_window { paint(rem, Style::Accent) } else { rem });
+        timeline.push('|');
+    }
+    timeline.pop();
+    println!("{} {} {}", format.format(first.start), timeline, format.format(last.end));
+    println!("(─ light/deep sleep, █ REM, one mark per {} minutes)\n", CYCLE_SLOT_MINUTES);
+    
+    for (i, cycle) in cycles.iter().enumerate() {
+        let rem = match cycle.rem_start {
+            Some(start) => format!("REM ~{}-{}", format.format(start), format.format(cycle.end)),
+            None => "ends before REM".to_string(),
+        };
+        let marker = if cycle.lucid_window { paint("  ★ best window for lucidity", Style::Accent) } else { String::new() };
+        println!("Cycle {}: {}-{}  {}{}", i + 1, format.format(cycle.start), format.format(cycle.end), rem, marker);
+    }
+}
+
+fn repair_daily_logs(journal: &Journal) -> anyhow::Result<()> {
+    let merged = journal.repair_daily_logs()?;
+    if merged.is_empty() {
+        println!("No duplicate daily logs found.");
+        return Ok(());
+    }
+    
+    for (date, count) in &merged {
+        println!("Merged {} logs for {} into one.", count, date);
+    }
+    println!("Repaired {} date(s).", merged.len());
+    Ok(())
+}
+
+fn prune_dreams(journal: &Journal, min_words: usize, dry_run: bool, yes: bool, force: bool) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    let protected: Vec<u32> = if force {
+        Vec::new()
+    } else {
+        journal.daily_logs()?
+            .iter()
+            .filter_map(|log| log.dream.as_ref())
+            .filter(|d| d.lucid == Some(true))
+            .map(|d| d.id)
+            .collect()
+    };
+
+    let candidates: Vec<&Dream> = dreams.iter()
+        .filter(|d| word_count(&d.content) < min_words)
+        .filter(|d| !protected.contains(&d.id))
+        .collect();
+
+    if candidates.is_empty() {
+        println!("No dreams shorter than {} words.", min_words);
+        return Ok(());
+    }
+
+    println!("{:<5} {:<12} {:<30} {:<6}", "ID", "Date", "Title", "Words");
+    for dream in &candidates {
+        println!("{:<5} {:<12} {:<30} {:<6}",
+            dream.id, dream.date.to_string(), dream.title, word_count(&dream.content));
+    }
+
+    if dry_run {
+        println!("\n{} dreams would be pruned (dry run).", candidates.len());
+        return Ok(());
+    }
+
+    if !yes {
+        let answer = prompt("CONFIRM", &format!("Delete {} dreams? (y/n): ", candidates.len()))?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Prune cancelled.");
+            return Ok(());
+        }
+    }
+
+    let pruned: Vec<u32> = candidates.iter().map(|d| d.id).collect();
+    let removed = journal.delete_dreams(&pruned, "dream prune")?;
+    println!("Pruned {} dreams. Run `dream undo` to restore them.", removed);
+
+    Ok(())
+}
+
+fn delete_dream(journal: &Journal, id: u32, yes: bool) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    let dream = dreams.iter().find(|d| d.id == id)
+        .ok_or_else(|| JournalError::NotFound(format!("Dream #{} not found", id)))?;
+
+    if !yes {
+        let answer = prompt("CONFIRM", &format!("Delete dream #{} \"{}\"? (y/n): ", dream.id, dream.title))?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            println!("Delete cancelled.");
+            return Ok(());
+        }
+    }
+
+    journal.delete_dreams(&[id], &format!("dream delete {}", id))?;
+    println!("Dream #{} deleted. Run `dream undo` to restore it.", id);
+
     Ok(())
 }
 
-fn show_statistics() -> anyhow::Result<()> {
-    let dreams = load_dreams()?;
-    let daily_logs = load_daily_logs()?;
+fn undo(journal: &Journal) -> anyhow::Result<()> {
+    match journal.undo()? {
+        Some(record) => println!("Restored {} from before `{}` ({}).", record.file, record.operation, record.created),
+        None => println!("Nothing to undo."),
+    }
+    Ok(())
+}
+
+fn show_statistics(journal: &Journal) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    let daily_logs = journal.daily_logs()?;
+    let config = journal.config()?;
+    let scale = config.quality_scale;
+    let mut out = String::new();
     
-    let sleep_logs: Vec<_> = daily_logs.iter()
-        .filter_map(|log| log.sleep.as_ref())
+    let sleep_logs: Vec<&DailyLog> = daily_logs.iter()
+        .filter(|log| !log.sleep.is_empty())
         .collect();
     
-    println!("\n--- DREAM & SLEEP STATISTICS ---");
+    writeln!(out, "\n--- DREAM & SLEEP STATISTICS ---")?;
     
-    println!("\nDREAM STATS:");
-    println!("Total dreams recorded: {}", dreams.len());
+    writeln!(out, "\nDREAM STATS:")?;
+    writeln!(out, "Total dreams recorded: {}", dreams.len())?;
     
     let lucid_dreams = dreams.iter()
         .filter(|d| d.lucid == Some(true))
         .count();
-    println!("Lucid dreams: {} ({:.1}%)", 
+    writeln!(out, "Lucid dreams: {} ({:.1}%)", 
         lucid_dreams,
         if !dreams.is_empty() {
             (lucid_dreams as f32 / dreams.len() as f32) * 100.0
         } else {
             0.0
         }
-    );
+    )?;
     
-    if !dreams.is_empty() {
-        let total_words: usize = dreams.iter()
-            .map(|d| d.content.split_whitespace().count())
-            .sum();
-        println!("Average dream length: {} words", total_words / dreams.len());
+    let lengths: Vec<(usize, &Dream)> = dreams.iter()
+        .map(|d| (word_count(&d.content), d))
+        .collect();
+    if let (Some((shortest, short_dream)), Some((longest, long_dream))) = (
+        lengths.iter().min_by_key(|(words, d)| (*words, d.id)),
+        lengths.iter().max_by_key(|(words, d)| (*words, std::cmp::Reverse(d.id))),
+    ) {
+        let words: Vec<f32> = lengths.iter().map(|(words, _)| *words as f32).collect();
+        writeln!(out, "Average dream length: {} words", lengths.iter().map(|(words, _)| words).sum::<usize>() / lengths.len())?;
+        writeln!(out, "Median dream length: {:.0} words", median(&words).unwrap_or_default())?;
+        writeln!(out, "Shortest: {} words (#{} {})", shortest, short_dream.id, short_dream.title)?;
+        writeln!(out, "Longest: {} words (#{} {})", longest, long_dream.id, long_dream.title)?;
+        
+        if lengths.len() > 1 {
+            writeln!(out, "\nDream length distribution:")?;
+            for (low, width, count) in histogram(&words, DREAM_LENGTH_BIN_WORDS, DREAM_LENGTH_BINS) {
+                let label = format!("{:.0}-{:.0} words", low, low + width - 1.0);
+                writeln!(out, "{:>15} | {} {}", label, "▇".repeat(count), count)?;
+            }
+        }
+    }
+    
+    let vividness: Vec<f32> = dreams.iter().filter_map(|d| d.vividness).map(f32::from).collect();
+    if !vividness.is_empty() {
+        writeln!(out, "Average recall vividness: {:.1}/{} ({} rated dreams)",
+            vividness.iter().sum::<f32>() / vividness.len() as f32, VIVIDNESS_SCALE, vividness.len())?;
     }
     
     let mut word_counts = HashMap::new();
     for dream in &dreams {
-        for word in dream.content.split_whitespace() {
-            let word = word.to_lowercase().trim_matches(|c: char| !c.is_alphanumeric()).to_string();
-            if !word.is_empty() {
-                *word_counts.entry(word).or_insert(0) += 1;
-            }
+        for word in tokenize(&dream.content) {
+            *word_counts.entry(word).or_insert(0) += 1;
         }
     }
     
     let mut sorted_words: Vec<_> = word_counts.iter().collect();
-    sorted_words.sort_by(|a, b| b.1.cmp(a.1));
+    sorted_words.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
     
     if !sorted_words.is_empty() {
-        println!("\nMost frequent dream words:");
+        writeln!(out, "\nMost frequent dream words:")?;
         for (i, (word, count)) in sorted_words.iter().take(10).enumerate() {
-            println!("{}. {} ({} occurrences)", i + 1, word, count);
+            writeln!(out, "{}. {} ({} occurrences)", i + 1, word, count)?;
         }
     }
     
-    println!("\nSLEEP STATS:");
+    let settings = setting_counts(&dreams);
+    if !settings.is_empty() {
+        let placed: u32 = settings.iter().map(|s| s.dreams).sum();
+        writeln!(out, "\nDream settings ({} of {} dreams):", placed, dreams.len())?;
+        for setting in &settings {
+            writeln!(out, "  {}: {} dreams ({:.0}%), {:.0}% lucid",
+                setting.setting, setting.dreams, setting.dreams as f32 * 100.0 / placed as f32, setting.lucid_rate())?;
+        }
+        if let Some(best) = settings.iter()
+            .filter(|s| s.lucid > 0)
+            .max_by(|a, b| a.lucid_rate().total_cmp(&b.lucid_rate()).then(a.dreams.cmp(&b.dreams))) {
+            writeln!(out, "Most often lucid in: {} ({} of {} dreams)", best.setting, best.lucid, best.dreams)?;
+        }
+    }
+    
+    writeln!(out, "\nSLEEP STATS:")?;
     if sleep_logs.is_empty() {
-        println!("No sleep data recorded yet.");
+        writeln!(out, "No sleep data recorded yet.")?;
     } else {
         let mut total_duration = 0.0;
         let mut total_quality = 0.0;
         let mut sleep_durations = Vec::new();
         
         for log in &sleep_logs {
-            if let Ok(bedtime) = NaiveTime::parse_from_str(&log.bedtime, "%H:%M") {
-                if let Ok(wake_time) = NaiveTime::parse_from_str(&log.wake_time, "%H:%M") {
-                    let mut duration = (wake_time - bedtime).num_minutes() as f32 / 60.0;
-                    if duration < 0.0 {
-                        duration += 24.0;
-                    }
-                    total_duration += duration;
-                    sleep_durations.push(duration);
-                }
+            if let Some(minutes) = log.total_sleep_minutes() {
+                let duration = minutes as f32 / 60.0;
+                total_duration += duration;
+                sleep_durations.push(duration);
             }
-            total_quality += log.quality as f32;
+            total_quality += log.sleep_quality().unwrap_or_default();
         }
         
         let avg_duration = total_duration / sleep_logs.len() as f32;
@@ -842,27 +2405,69 @@ fn show_statistics() -> anyhow::Result<()> {
         let min_duration = sleep_durations.iter().fold(f32::INFINITY, |a, &b| a.min(b));
         let max_duration = sleep_durations.iter().fold(0.0_f32, |a, &b| a.max(b));
         
-        println!("Nights tracked: {}", sleep_logs.len());
-        println!("Average sleep duration: {:.1} hours", avg_duration);
-        println!("Min sleep: {:.1}h, Max sleep: {:.1}h", min_duration, max_duration);
-        println!("Average sleep quality: {:.1}/5", avg_quality);
+        writeln!(out, "Nights tracked: {}", sleep_logs.len())?;
+        let naps: usize = sleep_logs.iter().map(|log| log.sleep.len() - 1).sum();
+        if naps > 0 {
+            writeln!(out, "Naps and extra sleeps: {} (counted in their day's totals)", naps)?;
+        }
+        writeln!(out, "Average sleep duration: {:.1} hours", avg_duration)?;
+        writeln!(out, "Min sleep: {:.1}h, Max sleep: {:.1}h", min_duration, max_duration)?;
+        writeln!(out, "Average sleep quality: {:.1}/{}", avg_quality, scale)?;
+        
+        let qualities: Vec<f32> = sleep_logs.iter().filter_map(|log| log.sleep_quality()).collect();
+        if sleep_durations.len() >= MIN_PERCENTILE_NIGHTS {
+            let [p25, p50, p75] = [25.0, 50.0, 75.0].map(|p| percentile(&sleep_durations, p).unwrap_or_default());
+            writeln!(out, "Sleep duration p25 / median / p75: {:.1}h / {:.1}h / {:.1}h", p25, p50, p75)?;
+        }
+        if qualities.len() >= MIN_PERCENTILE_NIGHTS {
+            let [p25, p50, p75] = [25.0, 50.0, 75.0].map(|p| percentile(&qualities, p).unwrap_or_default());
+            writeln!(out, "Sleep quality p25 / median / p75: {:.1} / {:.1} / {:.1} (of {})", p25, p50, p75, scale)?;
+        }
+        if sleep_durations.len().min(qualities.len()) < MIN_PERCENTILE_NIGHTS {
+            writeln!(out, "(Median and quartiles appear once {} nights are tracked.)", MIN_PERCENTILE_NIGHTS)?;
+        }
+        
+        let off_scale = sleep_logs.iter().flat_map(|log| &log.sleep).filter(|s| s.quality > scale).count();
+        if off_scale > 0 {
+            writeln!(out, "Note: {} sleeps are rated above the current 1-{} scale; they were probably logged on a different scale.",
+                off_scale, scale)?;
+        }
+        
+        let target = config.sleep_target_hours;
+        let debt = sleep_debt_hours(sleep_logs.iter().flat_map(|log| &log.sleep), target, Utc::now().date_naive(), SLEEP_DEBT_DAYS);
+        writeln!(out, "Sleep target: {:.1}h, average is {:+.1}h", target, avg_duration - target)?;
+        writeln!(out, "Sleep debt (last {} nights): {:.1}h", SLEEP_DEBT_DAYS, debt)?;
+        
+        let mut recent: Vec<&SleepLog> = sleep_logs.iter().filter_map(|log| log.main_sleep()).collect();
+        recent.sort_by_key(|s| s.date);
+        let recent = &recent[recent.len().saturating_sub(REGULARITY_NIGHTS)..];
+        if let Some(regularity) = sleep_regularity(recent.iter().copied()) {
+            writeln!(out, "Sleep regularity score (last {} nights): {}/100 (bedtime ±{:.0} min, wake time ±{:.0} min)",
+                regularity.nights, regularity.score, regularity.bedtime_spread_minutes, regularity.wake_spread_minutes)?;
+            if regularity.drifting() {
+                writeln!(out, "{}", paint("Your bedtime or wake time is drifting by more than an hour.", Style::Bad))?;
+            }
+            if regularity.score < POOR_REGULARITY_SCORE {
+                writeln!(out, "Tip: going to bed and getting up at the same times makes REM timing, and WBTB alarms, more predictable.")?;
+            }
+        }
         
         let lucid_nights = daily_logs.iter()
             .filter(|log| 
-                log.dream.as_ref().map_or(false, |d| d.lucid == Some(true)) &&
-                log.sleep.is_some()
+                log.dream.as_ref().is_some_and(|d| d.lucid == Some(true)) &&
+                !log.sleep.is_empty()
             )
             .count();
         
         if lucid_nights > 0 {
-            println!("\nLucid dreams occurred on {:.1}% of tracked nights", 
-                (lucid_nights as f32 / sleep_logs.len() as f32) * 100.0);
+            writeln!(out, "\nLucid dreams occurred on {:.1}% of tracked nights", 
+                (lucid_nights as f32 / sleep_logs.len() as f32) * 100.0)?;
         }
         
         let lucid_quality: f32 = daily_logs.iter()
             .filter_map(|log| 
-                if log.dream.as_ref().map_or(false, |d| d.lucid == Some(true)) {
-                    log.sleep.as_ref().map(|s| s.quality as f32)
+                if log.dream.as_ref().is_some_and(|d| d.lucid == Some(true)) {
+                    log.sleep_quality()
                 } else {
                     None
                 }
@@ -870,72 +2475,309 @@ fn show_statistics() -> anyhow::Result<()> {
             .sum();
         
         if lucid_nights > 0 {
-            println!("Average sleep quality on lucid nights: {:.1}/5", 
-                lucid_quality / lucid_nights as f32);
+            writeln!(out, "Average sleep quality on lucid nights: {:.1}/{}", 
+                lucid_quality / lucid_nights as f32, scale)?;
         }
         
-        println!("\nSleep duration consistency:");
-        for duration in sleep_durations.iter().take(30) {
-            println!("{:.1}h: {}", duration, "▇".repeat((*duration * 2.0) as usize));
+        writeln!(out, "\nSleep duration distribution:")?;
+        for (low, width, count) in histogram(&sleep_durations, 0.5, SLEEP_HISTOGRAM_BINS) {
+            let label = format!("{:.1}-{:.1}h", low, low + width);
+            writeln!(out, "{:>10} | {} {}", label, "▇".repeat(count), count)?;
         }
     }
     
-    println!("\nREALITY CHECKS:");
+    writeln!(out, "\nREALITY CHECKS:")?;
     let total_rc: u32 = daily_logs.iter().map(|log| log.reality_checks).sum();
-    println!("Total reality checks recorded: {}", total_rc);
+    writeln!(out, "Total reality checks recorded: {}", total_rc)?;
     
     if !daily_logs.is_empty() {
         let avg_rc = total_rc as f32 / daily_logs.len() as f32;
-        println!("Average per day: {:.1}", avg_rc);
+        writeln!(out, "Average per day: {:.1}", avg_rc)?;
         
         let max_rc = daily_logs.iter().map(|log| log.reality_checks).max().unwrap_or(0);
         let min_rc = daily_logs.iter().map(|log| log.reality_checks).min().unwrap_or(0);
-        println!("Most active day: {} checks, Least active: {}", max_rc, min_rc);
+        writeln!(out, "Most active day: {} checks, Least active: {}", max_rc, min_rc)?;
+    }
+    
+    let today = Utc::now().date_naive();
+
+    writeln!(out, "\nTRENDS (LAST {} DAYS):", TREND_DAYS)?;
+    let dream_counts: Vec<Option<f32>> = bucket_by_day(
+        dreams.iter().map(|d| (d.date, 1.0)),
+        today,
+    )
+    .iter()
+    .map(|day| Some(day.iter().sum()))
+    .collect();
+    let max_count = dream_counts.iter().flatten().fold(0.0_f32, |a, &b| a.max(b));
+    writeln!(out, "Dreams per day: {}", sparkline(&dream_counts, 0.0, max_count))?;
+
+    let quality_by_day: Vec<Option<f32>> = bucket_by_day(
+        sleep_logs.iter().filter_map(|log| Some((log.date, log.sleep_quality()?))),
+        today,
+    )
+    .iter()
+    .map(|day| {
+        if day.is_empty() {
+            None
+        } else {
+            Some(day.iter().sum::<f32>() / day.len() as f32)
+        }
+    })
+    .collect();
+    writeln!(out, "Sleep quality:  {}", sparkline(&quality_by_day, 1.0, scale as f32))?;
+
+    let vividness_by_day: Vec<Option<f32>> = bucket_by_day(
+        dreams.iter().filter_map(|d| Some((d.date, f32::from(d.vividness?)))),
+        today,
+    )
+    .iter()
+    .map(|day| (!day.is_empty()).then(|| day.iter().sum::<f32>() / day.len() as f32))
+    .collect();
+    if vividness_by_day.iter().any(Option::is_some) {
+        writeln!(out, "Vividness:      {}", sparkline(&vividness_by_day, 1.0, VIVIDNESS_SCALE as f32))?;
+        let (recent, earlier): (Vec<&Dream>, Vec<&Dream>) = dreams.iter()
+            .filter(|d| d.vividness.is_some())
+            .partition(|d| (today - d.date).num_days() < TREND_DAYS as i64);
+        let average = |rated: &[&Dream]| rated.iter().filter_map(|d| d.vividness).map(f32::from).sum::<f32>() / rated.len() as f32;
+        if !recent.is_empty() && !earlier.is_empty() {
+            let (recent, earlier) = (average(&recent), average(&earlier));
+            let direction = if recent > earlier { "sharpening" } else if recent < earlier { "fading" } else { "steady" };
+            writeln!(out, "Recall is {}: {:.1}/{} over the last {} days vs {:.1}/{} before",
+                direction, recent, VIVIDNESS_SCALE, TREND_DAYS, earlier, VIVIDNESS_SCALE)?;
+        }
+    }
+
+    let mut tag_rates: HashMap<&str, (u32, u32, u32)> = HashMap::new();
+    for log in &daily_logs {
+        for tag in &log.tags {
+            let entry = tag_rates.entry(tag.as_str()).or_insert((0, 0, 0));
+            entry.0 += 1;
+            if let Some(dream) = &log.dream {
+                entry.1 += 1;
+                if dream.lucid == Some(true) {
+                    entry.2 += 1;
+                }
+            }
+        }
+    }
+
+    if !tag_rates.is_empty() {
+        writeln!(out, "\nDAY TAGS:")?;
+        let mut sorted_tags: Vec<_> = tag_rates.iter().collect();
+        sorted_tags.sort_by(|a, b| b.1.0.cmp(&a.1.0).then(a.0.cmp(b.0)));
+        for (tag, (days, recalled, lucid)) in sorted_tags {
+            writeln!(out, "{}: {} days, recall {:.1}%, lucid {:.1}%",
+                tag,
+                days,
+                (*recalled as f32 / *days as f32) * 100.0,
+                (*lucid as f32 / *days as f32) * 100.0)?;
+        }
+    }
+
+    writeln!(out, "\nDREAM CALENDAR:")?;
+    let mut dream_calendar = HashMap::new();
+    for dream in &dreams {
+        *dream_calendar.entry(dream.date).or_insert(0) += 1;
+    }
+    
+    let mut sorted_dates: Vec<_> = dream_calendar.iter().collect();
+    sorted_dates.sort_by_key(|(date, _)| **date);
+
+    for (date, count) in sorted_dates.iter().take(30) {
+        writeln!(out, "{}: {} {}", date, "★".repeat(**count as usize), count)?;
+    }
+    
+    writeln!(out, "\n{}", paint("TECHNIQUE EFFECTIVENESS", Style::Heading))?;
+    if let Ok(stats) = journal.technique_effectiveness() {
+        for (technique, data) in rank_techniques(&stats) {
+            writeln!(out, "  {}: {:.1}% success ({} attempts)", 
+                technique, data.success_rate, data.attempts)?;
+        }
+    }
+    
+    page_output(&out)
+}
+
+fn compare_periods(journal: &Journal, first: &str, second: &str) -> anyhow::Result<()> {
+    let today = Utc::now().date_naive();
+    let week_start = journal.config()?.week_start.weekday();
+    let ranges = [DateRange::parse(first, today, week_start)?, DateRange::parse(second, today, week_start)?];
+    let dreams = journal.dreams()?;
+    let logs = journal.daily_logs()?;
+    let [a, b] = ranges.map(|range| period_summary(&dreams, &logs, range, today));
+    
+    decor!("\n{}", paint("PERIOD COMPARISON", Style::Heading));
+    decor!("===============================================");
+    for (label, range) in [(first, ranges[0]), (second, ranges[1])] {
+        println!("{}: {} to {}", label, range.start, range.end);
+    }
+    
+    println!("\n{:<22} {:>12} {:>12}  Change", "", first, second);
+    // (metric, first, second, decimals, unit)
+    let rows = [
+        ("Lucid rate", a.lucid_rate(), b.lucid_rate(), 0, "%"),
+        ("Dreams per week", a.dreams_per_week(), b.dreams_per_week(), 1, ""),
+        ("Lucid dreams", Some(a.lucid as f32), Some(b.lucid as f32), 0, ""),
+        ("Sleep quality", a.average_quality, b.average_quality, 1, ""),
+        ("Reality checks / day", a.checks_per_day(), b.checks_per_day(), 1, ""),
+    ];
+    for (metric, before, after, decimals, unit) in rows {
+        let cell = |value: Option<f32>| value.map_or("—".to_string(), |v| format!("{:.*}{}", decimals, v, unit));
+        let change = match (before, after) {
+            (Some(before), Some(after)) if before > 0.0 => {
+                let percent = (after - before) / before * 100.0;
+                if percent.abs() < 0.5 {
+                    "= 0%".to_string()
+                } else if percent > 0.0 {
+                    paint(format!("▲ +{:.0}%", percent), Style::Accent)
+                } else {
+                    paint(format!("▼ {:.0}%", percent), Style::Bad)
+                }
+            }
+            (Some(_), Some(after)) if after > 0.0 => paint("▲ new", Style::Accent),
+            _ => "—".to_string(),
+        };
+        println!("{:<22} {:>12} {:>12}  {}", metric, cell(before), cell(after), change);
+    }
+    
+    if a.dreams == 0 && a.logged_days == 0 || b.dreams == 0 && b.logged_days == 0 {
+        decor!("\nOne of the periods has no dreams or daily logs, so there is little to compare.");
+    }
+    
+    Ok(())
+}
+
+/// The marker `report` left on its last `--since-last` run, saying so when there's none yet.
+fn since_last_marker(journal: &Journal, report: &str) -> anyhow::Result<Option<ReportMarker>> {
+    let marker = journal.report_marker(report)?;
+    match &marker {
+        Some(marker) => {
+            let time_format = journal.config()?.time_format;
+            decor!("Showing what's new since {}", time_format.format_datetime(&marker.at.with_timezone(&Local)));
+        }
+        None => decor!("First --since-last run; showing everything. Next time only what's new is shown."),
+    }
+    Ok(marker)
+}
+
+fn analyze(journal: &Journal, since_last: bool) -> anyhow::Result<()> {
+    let mut dreams = journal.dreams()?;
+    let mut daily_logs = journal.daily_logs()?;
+    let time_format = journal.config()?.time_format;
+    if since_last
+        && let Some(marker) = since_last_marker(journal, "analyze")? {
+        dreams.retain(|d| marker.is_new_dream(d));
+        daily_logs.retain(|log| marker.is_new_log(log));
+    }
+
+    println!("\n--- LUCID DREAM TIMING ---");
+    let lucid_windows: Vec<_> = daily_logs.iter()
+        .filter(|log| log.dream.as_ref().is_some_and(|d| d.lucid == Some(true)))
+        .filter_map(|log| log.main_sleep().and_then(rem_window))
+        .collect();
+
+    if lucid_windows.is_empty() {
+        println!("No lucid nights with sleep data yet.");
+    } else {
+        let mut hours = [0u32; 24];
+        for (start, end) in &lucid_windows {
+            let mut hour = start.hour();
+            loop {
+                hours[hour as usize] += 1;
+                if hour == end.hour() {
+                    break;
+                }
+                hour = (hour + 1) % 24;
+            }
+        }
+
+        println!("Likely lucid-dream times across {} lucid nights:", lucid_windows.len());
+        for hour in (12..24).chain(0..12) {
+            if hours[hour] > 0 {
+                let label = time_format.format(NaiveTime::from_hms_opt(hour as u32, 0, 0).unwrap());
+                println!("{:>8} {} {}", label, "█".repeat(hours[hour] as usize), hours[hour]);
+            }
+        }
     }
-    
-    println!("\nDREAM CALENDAR:");
-    let mut dream_calendar = HashMap::new();
-    for dream in &dreams {
-        *dream_calendar.entry(dream.date.clone()).or_insert(0) += 1;
+
+    if let Some((bedtime, minutes)) = typical_schedule(daily_logs.iter().filter_map(|log| log.main_sleep())) {
+        println!("\nTypical schedule: bed at {}, {:.1}h of sleep",
+            time_format.format(bedtime), minutes as f32 / 60.0);
+        println!("Set your WBTB alarm at ~{}", time_format.format(suggested_wbtb_time(bedtime, minutes)));
     }
-    
-    let mut sorted_dates: Vec<_> = dream_calendar.iter().collect();
-    sorted_dates.sort_by_key(|(date, _)| (*date).clone());
 
-    for (date, count) in sorted_dates.iter().take(30) {
-        println!("{}: {} {}", date, "★".repeat(**count as usize), count);
+    println!("\n--- RECURRING THEMES ---");
+    let contents = || dreams.iter().map(|d| d.content.as_str());
+    for (n, label) in [(2, "2-word"), (3, "3-word")] {
+        let phrases: Vec<_> = top_ngrams(contents(), n, 10)
+            .into_iter()
+            .filter(|(_, count)| *count > 1)
+            .collect();
+        if !phrases.is_empty() {
+            println!("Recurring {} phrases:", label);
+            for (i, (phrase, count)) in phrases.iter().enumerate() {
+                println!("{}. \"{}\" ({} occurrences)", i + 1, phrase, count);
+            }
+        }
     }
-    
-    println!("\n\x1b[1;34mTECHNIQUE EFFECTIVENESS\x1b[0m");
-    if let Ok(stats) = calculate_technique_effectiveness() {
-        for (technique, data) in stats {
-            println!("  {}: {:.1}% success ({} attempts)", 
-                technique, data.success_rate, data.attempts);
+
+    // Both windows come from the whole journal: --since-last would leave the earlier one empty.
+    let all_dreams = journal.dreams()?;
+    let today = Utc::now().date_naive();
+    let (recent, earlier): (Vec<&Dream>, Vec<&Dream>) = all_dreams.iter()
+        .partition(|d| (today - d.date).num_days() < TREND_DAYS as i64);
+    let (rising, falling) = term_trends(recent.iter().map(|d| d.content.as_str()), earlier.iter().map(|d| d.content.as_str()), 5);
+    if !rising.is_empty() || !falling.is_empty() {
+        println!("\nLast {} days against earlier dreams (mentions per 1000 words):", TREND_DAYS);
+        for (label, trends) in [("Rising", &rising), ("Fading", &falling)] {
+            if !trends.is_empty() {
+                let terms: Vec<String> = trends.iter()
+                    .map(|t| format!("{} {:.1} (was {:.1})", t.word, t.recent, t.earlier))
+                    .collect();
+                println!("  {}: {}", label, terms.join(", "));
+            }
+        }
+    }
+
+    println!("\n--- DREAM SIGN CLUSTERS ---");
+    let pairs = sign_pairs(&dreams);
+    if pairs.is_empty() {
+        println!("No dream signs have appeared together more than once yet.");
+    } else {
+        println!("Signs that show up together:");
+        for pair in pairs.iter().take(10) {
+            println!("  {} + {}: {} dreams, {:.0}% lucid", pair.first, pair.second, pair.together, pair.lucid_rate());
+        }
+        
+        let mut predictive: Vec<&SignPair> = pairs.iter().filter(|p| p.lucid > 0).collect();
+        predictive.sort_by(|a, b| b.lucid_rate().total_cmp(&a.lucid_rate()).then(b.together.cmp(&a.together)));
+        if !predictive.is_empty() {
+            println!("Clusters most linked to lucidity (good reality-check triggers):");
+            for pair in predictive.iter().take(3) {
+                println!("  {} + {} ({} of {} lucid)", pair.first, pair.second, pair.lucid, pair.together);
+            }
         }
     }
-    
-    Ok(())
-}
 
-fn load_daily_logs() -> anyhow::Result<Vec<DailyLog>> {
-    if !Path::new(DAILY_LOG_FILE).exists() {
-        return Ok(Vec::new());
+    if since_last {
+        journal.mark_report("analyze")?;
     }
-    let data = fs::read_to_string(DAILY_LOG_FILE)?;
-    Ok(serde_json::from_str(&data)?)
+    Ok(())
 }
 
-fn daily_entry() -> anyhow::Result<()> {
-    let today = Utc::now().format("%Y-%m-%d").to_string();
-    let mut logs = load_daily_logs()?;
+/// Asks for the day's sleep, dream and metrics. With `dry_run`, the answers are thrown away at the end.
+fn daily_entry(journal: &Journal, notes: NotesInput, dry_run: bool) -> anyhow::Result<()> {
+    let today = Utc::now().date_naive();
+    let mut logs = journal.daily_logs()?;
+    let config = journal.config()?;
+    let time_format = config.time_format;
+    let scale = config.quality_scale.max(1);
     
     if let Some(log) = logs.iter().find(|l| l.date == today) {
         println!("Daily entry already exists for today:");
-        print_daily_summary(log);
-        print!("Do you want to update it? (y/n): ");
-        io::stdout().flush()?;
-        let mut answer = String::new();
-        io::stdin().read_line(&mut answer)?;
+        print_daily_summary(log, time_format, scale);
+        let answer = prompt("CONFIRM", "Do you want to update it? (y/n): ")?;
         
         if !answer.trim().eq_ignore_ascii_case("y") {
             return Ok(());
@@ -943,179 +2785,377 @@ fn daily_entry() -> anyhow::Result<()> {
     }
 
     let mut new_log = DailyLog {
-        date: today.clone(),
+        date: today,
         dream: None,
-        sleep: None,
+        sleep: Vec::new(),
         wake_feeling: None,
         reality_checks: 0,
         notes: String::new(),
         technique_practice: None,
         wbtb_alarm_used: None,
+        tags: Vec::new(),
+        wbtb_awake_minutes: None,
     };
 
     println!("\n--- SLEEP LOG ---");
-    print!("Bedtime last night (HH:MM): ");
-    io::stdout().flush()?;
-    let mut bedtime = String::new();
-    io::stdin().read_line(&mut bedtime)?;
+    let last_night = journal.alarms()?.into_iter()
+        .filter(|a| a.last_triggered == Some(today))
+        .max_by_key(|a| a.id);
+    if let Some(alarm) = &last_night {
+        println!("Pre-filled from last night's alarm #{} (press Enter to keep, or type a new time).", alarm.id);
+    }
     
-    print!("Wake time today (HH:MM): ");
-    io::stdout().flush()?;
-    let mut wake_time = String::new();
-    io::stdin().read_line(&mut wake_time)?;
+    let bedtime = prompt_time("BEDTIME", "Bedtime last night", last_night.as_ref().map(|a| a.bedtime.as_str()))?;
+    let wake_time = prompt_time("WAKE_TIME", "Wake time today", last_night.as_ref().map(|a| a.wake_time.as_str()))?;
     
-    print!("Sleep quality (1-5): ");
-    io::stdout().flush()?;
-    let mut quality_input = String::new();
-    io::stdin().read_line(&mut quality_input)?;
-    let quality = quality_input.trim().parse::<u8>()?.clamp(1, 5);
-    
-    new_log.sleep = Some(SleepLog {
-        date: today.clone(),
-        bedtime: bedtime.trim().to_string(),
-        wake_time: wake_time.trim().to_string(),
-        quality,
+    new_log.sleep.push(SleepLog {
+        date: today,
+        bedtime,
+        wake_time,
+        quality: prompt_quality("QUALITY", scale)?,
         notes: String::new(),
     });
+    
+    loop {
+        let answer = prompt_optional("NAP", "Add another sleep session, like a nap? (y/N): ")?;
+        if !answer.trim().eq_ignore_ascii_case("y") {
+            break;
+        }
+        let bedtime = prompt_time("NAP_BEDTIME", "Fell asleep at", None)?;
+        let wake_time = prompt_time("NAP_WAKE_TIME", "Woke up at", None)?;
+        new_log.sleep.push(SleepLog {
+            date: today,
+            bedtime,
+            wake_time,
+            quality: prompt_quality("NAP_QUALITY", scale)?,
+            notes: String::new(),
+        });
+        // Preset answers would ask for the same nap forever.
+        if noninteractive() {
+            break;
+        }
+    }
 
     println!("\n--- DREAM RECALL ---");
-    print!("Do you remember a dream? (y/n): ");
-    io::stdout().flush()?;
-    let mut answer = String::new();
-    io::stdin().read_line(&mut answer)?;
+    let answer = prompt("DREAM", "Do you remember a dream? (y/n): ")?;
     
     if answer.trim().eq_ignore_ascii_case("y") {
-        let mut dreams = load_dreams()?;
-        let id = dreams.last().map_or(1, |d| d.id + 1);
-        
-        print!("Dream title: ");
-        io::stdout().flush()?;
-        let mut title = String::new();
-        io::stdin().read_line(&mut title)?;
-        
-        println!("Dream content (Ctrl+D when finished):");
-        let mut content = String::new();
-        io::stdin().read_to_string(&mut content)?;
-        
-        print!("Was it a lucid dream? (y/n): ");
-        io::stdout().flush()?;
-        let mut lucid = String::new();
-        io::stdin().read_line(&mut lucid)?;
-        let is_lucid = lucid.trim().eq_ignore_ascii_case("y");
-        
-        print!("Did you notice any dream sign? (optional): ");
-        io::stdout().flush()?;
-        let mut sign = String::new();
-        io::stdin().read_line(&mut sign)?;
-        
-        let mut tags = vec![];
-        if is_lucid {
-            tags.push("#lucid".to_string());
-        }
-        
-        let dream = Dream {
-            id,
-            date: today.clone(),
-            title: title.trim().to_string(),
-            content: content.trim().to_string(),
-            tags,
-            lucid: Some(is_lucid),
-            dream_sign: if sign.trim().is_empty() {
-                None
-            } else {
-                Some(sign.trim().to_string())
-            },
-        };
-        
-        dreams.push(dream.clone());
-        save_dreams(&dreams)?;
-        new_log.dream = Some(dream);
+        new_log.dream = prompt_daily_dream(journal, today, dry_run)?;
     }
 
     println!("\n--- DAILY METRICS ---");
-    print!("How do you feel after waking up?: ");
-    io::stdout().flush()?;
-    let mut feeling = String::new();
-    io::stdin().read_line(&mut feeling)?;
-    new_log.wake_feeling = Some(feeling.trim().to_string());
+    let feeling = prompt_paragraph("WAKE_FEELING", "How do you feel after waking up?", notes)?;
+    new_log.wake_feeling = Some(feeling);
     
-    print!("Number of reality checks performed: ");
-    io::stdout().flush()?;
-    let mut rc_input = String::new();
-    io::stdin().read_line(&mut rc_input)?;
+    let rc_input = prompt("REALITY_CHECKS", "Number of reality checks performed: ")?;
     new_log.reality_checks = rc_input.trim().parse().unwrap_or(0);
     
-    println!("Additional notes (optional):");
-    let mut notes = String::new();
-    io::stdin().read_line(&mut notes)?;
-    new_log.notes = notes.trim().to_string();
+    if !preset_missing("NOTES") {
+        new_log.notes = prompt_paragraph("NOTES", "Additional notes (optional)", notes)?;
+    }
+
+    let tags_input = prompt_optional("DAY_TAGS", "Day tags (comma separated, e.g. #stressful, #caffeine): ")?;
+    new_log.tags = tags_input.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
 
     println!("\n--- WAKE BACK TO BED ---");
-    let alarms = load_alarms()?;
+    let alarms = journal.alarms()?;
     if !alarms.is_empty() {
         println!("Active alarms:");
         for alarm in &alarms {
             println!("[{}] Bed: {}, Wake: {}, Awake: {} min", 
-                alarm.id, alarm.bedtime, alarm.wake_time, alarm.awake_minutes);
+                alarm.id, time_format.format_str(&alarm.bedtime), time_format.format_str(&alarm.wake_time), alarm.awake_minutes);
         }
         
-        print!("Did you use a WBTB alarm? (enter ID or 0 for none): ");
-        io::stdout().flush()?;
-        let mut alarm_choice = String::new();
-        io::stdin().read_line(&mut alarm_choice)?;
-        if let Ok(id) = alarm_choice.trim().parse::<u32>() {
-            if id > 0 && alarms.iter().any(|a| a.id == id) {
-                new_log.wbtb_alarm_used = Some(id);
-                
-                print!("Was it successful? (y/n): ");
-                io::stdout().flush()?;
-                let mut success = String::new();
-                io::stdin().read_line(&mut success)?;
-                
-                let mut alarms = load_alarms()?;
-                if let Some(alarm) = alarms.iter_mut().find(|a| a.id == id) {
-                    alarm.last_triggered = Some(today.clone());
-                    alarm.success = Some(success.trim().eq_ignore_ascii_case("y"));
-                }
-                save_alarms(&alarms)?;
+        let alarm_choice = prompt("WBTB_ALARM", "Did you use a WBTB alarm? (enter ID or 0 for none): ")?;
+        if let Ok(id) = alarm_choice.trim().parse::<u32>()
+            && id > 0 && alarms.iter().any(|a| a.id == id) {
+            new_log.wbtb_alarm_used = Some(id);
+            
+            // A lucid dream that night answers the question, unless you say otherwise.
+            let lucid_night = new_log.dream.as_ref().is_some_and(|d| d.lucid == Some(true));
+            let label = if lucid_night {
+                "Your dream was lucid, so the alarm counts as a success. Was it successful? (Y/n): "
+            } else {
+                "Was it successful? (y/n): "
+            };
+            let answer = if lucid_night { prompt_optional("WBTB_SUCCESS", label)? } else { prompt("WBTB_SUCCESS", label)? };
+            
+            let planned = alarms.iter().find(|a| a.id == id).map_or(0, |a| a.awake_minutes);
+            let awake = prompt_optional("WBTB_AWAKE", &format!("How many minutes did you actually stay awake? [{}]: ", planned))?;
+            new_log.wbtb_awake_minutes = Some(awake.parse().unwrap_or(planned));
+            
+            let mut alarms = journal.alarms()?;
+            if let Some(alarm) = alarms.iter_mut().find(|a| a.id == id) {
+                alarm.last_triggered = Some(today);
+                alarm.success = Some(answer.eq_ignore_ascii_case("y") || (lucid_night && answer.is_empty()));
+                alarm.success_inferred = lucid_night && answer.is_empty();
+            }
+            if !dry_run {
+                journal.save_alarms(&alarms)?;
             }
         }
     }
 
+    if dry_run {
+        println!("\nDaily entry completed! (dry run — nothing saved)");
+        return Ok(());
+    }
+
     if let Some(index) = logs.iter().position(|l| l.date == today) {
         logs[index] = new_log;
     } else {
         logs.push(new_log);
     }
     
-    save_daily_logs(&logs)?;
+    journal.save_daily_logs(&logs)?;
     println!("\nDaily entry completed!");
     
-    update_statistics()?;
-    generate_weekly_report()?;
+    journal.update_statistics(&[])?;
+    generate_weekly_report(journal, None)?;
+    print_dry_spell_nudge(journal)?;
     
     Ok(())
 }
 
-fn save_daily_logs(logs: &[DailyLog]) -> anyhow::Result<()> {
-    let data = serde_json::to_string_pretty(logs)?;
-    fs::write(DAILY_LOG_FILE, data)?;
+/// Set to 1 for headless runs: prompts then take their answers from `LUCID_ANSWER_<KEY>` variables
+/// (listed in the README) and fail straight away when one is missing, instead of waiting on stdin.
+const NONINTERACTIVE_VAR: &str = "LUCID_NONINTERACTIVE";
+
+fn noninteractive() -> bool {
+    std::env::var(NONINTERACTIVE_VAR).is_ok_and(|value| value == "1")
+}
+
+/// Prints `label` and reads one line, trimmed. In non-interactive mode the answer comes from
+/// `LUCID_ANSWER_<key>` instead.
+fn prompt(key: &str, label: &str) -> anyhow::Result<String> {
+    if noninteractive() {
+        let var = format!("LUCID_ANSWER_{}", key);
+        return std::env::var(&var).map(|answer| answer.trim().to_string()).map_err(|_| JournalError::InvalidInput(format!(
+            "{} is set and nothing answers \"{}\"; set {}", NONINTERACTIVE_VAR, label.trim().trim_end_matches(':'), var)).into());
+    }
+    print!("{}", label);
+    io::stdout().flush()?;
+    let mut input = String::new();
+    io::stdin().read_line(&mut input)?;
+    Ok(input.trim().to_string())
+}
+
+/// `prompt` for questions that can be skipped with Enter: a missing preset answer is an empty one.
+fn prompt_optional(key: &str, label: &str) -> anyhow::Result<String> {
+    if preset_missing(key) {
+        return Ok(String::new());
+    }
+    prompt(key, label)
+}
+
+/// Whether this is a non-interactive run with no `LUCID_ANSWER_<key>` set.
+fn preset_missing(key: &str) -> bool {
+    noninteractive() && std::env::var(format!("LUCID_ANSWER_{}", key)).is_err()
+}
+
+/// `prompt` for multi-line text, ended with Ctrl+D.
+fn prompt_text(key: &str, label: &str) -> anyhow::Result<String> {
+    if noninteractive() {
+        return prompt(key, label);
+    }
+    println!("{} (Ctrl+D when finished):", label);
+    let mut text = String::new();
+    io::stdin().read_to_string(&mut text)?;
+    Ok(text)
+}
+
+/// Free text that can run longer than a line, read the way `input` asks for. The result is trimmed.
+fn prompt_paragraph(key: &str, label: &str, input: NotesInput) -> anyhow::Result<String> {
+    let text = match input {
+        NotesInput::Line => return prompt(key, &format!("{}: ", label)),
+        _ if noninteractive() => return prompt(key, label),
+        NotesInput::Multiline => prompt_text(key, label)?,
+        NotesInput::Editor => edit_text(&format!("daily-{}.md", key.to_lowercase().replace('_', "-")), "")?,
+    };
+    Ok(text.trim().to_string())
+}
+
+/// Waits up to `timeout` for a q key press. Without a terminal to read keys from (non-interactive
+/// mode), it only waits, leaving Ctrl+C to stop.
+fn quit_pressed(timeout: Duration) -> anyhow::Result<bool> {
+    if noninteractive() {
+        thread::sleep(timeout);
+        return Ok(false);
+    }
+    if event::poll(timeout)?
+        && let Event::Key(key_event) = event::read()? {
+        return Ok(key_event.code == KeyCode::Char('q'));
+    }
+    Ok(false)
+}
+
+/// Reads an HH:MM time, falling back to `default` when the answer is left empty.
+fn prompt_time(key: &str, label: &str, default: Option<&str>) -> anyhow::Result<String> {
+    let input = match default {
+        Some(default) => prompt_optional(key, &format!("{} (HH:MM) [{}]: ", label, default))?,
+        None => prompt(key, &format!("{} (HH:MM): ", label))?,
+    };
+    
+    Ok(match default {
+        Some(default) if input.is_empty() => default.to_string(),
+        _ => input,
+    })
+}
+
+fn prompt_quality(key: &str, scale: u8) -> anyhow::Result<u8> {
+    let input = prompt(key, &format!("Sleep quality (1-{}): ", scale))?;
+    let quality: u8 = input.trim().parse()
+        .map_err(|_| JournalError::InvalidInput(format!("Quality must be a number from 1 to {}", scale)))?;
+    Ok(quality.clamp(1, scale))
+}
+
+/// Asks how vivid the recall was; Enter skips it.
+fn prompt_vividness() -> anyhow::Result<Option<u8>> {
+    let input = prompt_optional("VIVIDNESS", &format!("How vivid was your recall? (1-{}, Enter to skip): ", VIVIDNESS_SCALE))?;
+    if input.trim().is_empty() {
+        return Ok(None);
+    }
+    let vividness: u8 = input.trim().parse()
+        .map_err(|_| JournalError::InvalidInput(format!("Vividness must be a number from 1 to {}", VIVIDNESS_SCALE)))?;
+    Ok(Some(vividness.clamp(1, VIVIDNESS_SCALE)))
+}
+
+/// Asks where the dream took place: a number from `DREAM_SETTINGS`, any other word, or Enter to skip.
+fn prompt_setting() -> anyhow::Result<Option<String>> {
+    let choices: Vec<String> = DREAM_SETTINGS.iter().enumerate().map(|(i, s)| format!("{}. {}", i + 1, s)).collect();
+    println!("Settings: {}", choices.join("  "));
+    let input = prompt_optional("SETTING", "Where did it mostly take place? (number or your own, Enter to skip): ")?;
+    Ok(match input.parse::<usize>() {
+        _ if input.is_empty() => None,
+        Ok(n) => Some(DREAM_SETTINGS.get(n.wrapping_sub(1))
+            .ok_or_else(|| JournalError::InvalidInput(format!("Pick a setting from 1 to {}", DREAM_SETTINGS.len())))?
+            .to_string()),
+        Err(_) => Some(input.to_lowercase()),
+    })
+}
+
+/// Asks for a dream's tags, offering those suggested by the config's `tag_rules`: Enter keeps
+/// the suggestions, `-` drops them. Without suggestions, only asks when `always_ask` is set.
+fn prompt_tags(journal: &Journal, text: &str, always_ask: bool) -> anyhow::Result<Vec<String>> {
+    let suggested = suggest_tags(&journal.config()?.tag_rules, text);
+    let label = if suggested.is_empty() {
+        if !always_ask {
+            return Ok(Vec::new());
+        }
+        "Tags (comma separated): "
+    } else {
+        println!("Suggested tags: {}", suggested.join(", "));
+        "Tags (comma separated, Enter to accept, - for none): "
+    };
+    let input = prompt_optional("TAGS", label)?;
+    
+    Ok(match input.as_str() {
+        "" => suggested,
+        "-" => Vec::new(),
+        input => split_tags(input),
+    })
+}
+
+fn split_tags(input: &str) -> Vec<String> {
+    input.split(',')
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+/// Asks for the night's dream and adds it to the journal, unless `dry_run`.
+fn prompt_daily_dream(journal: &Journal, today: NaiveDate, dry_run: bool) -> anyhow::Result<Option<Dream>> {
+    let title = prompt("TITLE", "Dream title: ")?;
+
+    let content = prompt_dream_content(journal, false)?;
+
+    if title.trim().is_empty() || content.trim().is_empty() {
+        println!("Empty dream skipped — title and content are required.");
+        return Ok(None);
+    }
+
+    if !confirm_if_duplicate(journal, today, &title, &content)? {
+        println!("Duplicate dream skipped.");
+        return Ok(None);
+    }
+
+    let lucid = prompt("LUCID", "Was it a lucid dream? (y/n): ")?;
+    let is_lucid = lucid.trim().eq_ignore_ascii_case("y");
+
+    let sign = prompt_optional("DREAM_SIGN", "Did you notice any dream sign? (optional): ")?;
+
+    let mut tags = prompt_tags(journal, &format!("{} {}", title, content), false)?;
+    if is_lucid && !tags.iter().any(|t| t == "#lucid") {
+        tags.push("#lucid".to_string());
+    }
+    let vividness = prompt_vividness()?;
+    let setting = prompt_setting()?;
+
+    let dream = Dream {
+        id: 0,
+        date: today,
+        title: title.trim().to_string(),
+        content: content.trim().to_string(),
+        tags,
+        lucid: Some(is_lucid),
+        dream_sign: if sign.trim().is_empty() {
+            None
+        } else {
+            Some(sign.trim().to_string())
+        },
+        vividness,
+        setting,
+        ..Default::default()
+    };
+    if dry_run {
+        return Ok(Some(dream));
+    }
+    let dream = journal.add_dream(dream)?;
+    if is_lucid {
+        print_lucid_milestone(journal, &dream)?;
+    }
+    
+    Ok(Some(dream))
+}
+
+/// Congratulates on a first lucid dream or a round number of them.
+fn print_lucid_milestone(journal: &Journal, dream: &Dream) -> anyhow::Result<()> {
+    let Some(milestone) = lucid_milestone(&journal.dreams()?, dream) else {
+        return Ok(());
+    };
+    
+    let days = milestone.days_since_start;
+    if milestone.first() {
+        println!("\n{}", paint("🎉 Your first lucid dream! Congratulations!", Style::Heading));
+        if days > 0 {
+            println!("It took {} days from your first journal entry. Keep journaling — the next one tends to come sooner.", days);
+        } else {
+            println!("And on your very first day of journaling, too.");
+        }
+    } else {
+        println!("\n{}", paint(format!("🎉 Lucid dream #{}! That's a milestone.", milestone.count), Style::Heading));
+        println!("{} lucid dreams in {} days of journaling.", milestone.count, days + 1);
+    }
     Ok(())
 }
 
-fn generate_weekly_report() -> anyhow::Result<()> {
-    let dreams = load_dreams()?;
-    let now = Utc::now();
-    let one_week_ago = now - chrono::Duration::days(7);
+/// Recall for this week, or for everything added since `since` when given.
+fn generate_weekly_report(journal: &Journal, since: Option<&ReportMarker>) -> anyhow::Result<()> {
+    let dreams = journal.dreams()?;
+    let today = Utc::now().date_naive();
+    let week_start = match since {
+        Some(marker) => marker.at.date_naive(),
+        None => today.week(journal.config()?.week_start.weekday()).first_day(),
+    };
+    let days = (today - week_start).num_days() + 1;
     
     let weekly_dreams: Vec<_> = dreams.iter()
-        .filter(|d| {
-            if let Ok(dream_date) = NaiveDate::parse_from_str(&d.date, "%Y-%m-%d") {
-                let dream_datetime = dream_date.and_hms_opt(0, 0, 0).unwrap();
-                dream_datetime >= one_week_ago.naive_utc()
-            } else {
-                false
-            }
+        .filter(|d| match since {
+            Some(marker) => marker.is_new_dream(d),
+            None => (week_start..=today).contains(&d.date),
         })
         .collect();
     
@@ -1124,13 +3164,20 @@ fn generate_weekly_report() -> anyhow::Result<()> {
         .count();
     
     println!("\n--- Weekly Report ---");
-    println!("Dreams this week: {}", weekly_dreams.len());
+    let period = if since.is_some() {
+        println!("Since {}", week_start);
+        "since then"
+    } else {
+        println!("Week of {} ({}, day {} of 7)", week_start, week_start.format("%A"), days);
+        "this week"
+    };
+    println!("Dreams {}: {}", period, weekly_dreams.len());
     println!("Lucid dreams: {}", lucid_count);
-    println!("Dream frequency: {:.1} per day", weekly_dreams.len() as f32 / 7.0);
+    println!("Dream frequency: {:.1} per day", weekly_dreams.len() as f32 / days as f32);
     
     if !weekly_dreams.is_empty() {
         let total_words: usize = weekly_dreams.iter()
-            .map(|d| d.content.split_whitespace().count())
+            .map(|d| word_count(&d.content))
             .sum();
         println!("Average dream length: {} words", total_words / weekly_dreams.len());
     }
@@ -1138,47 +3185,265 @@ fn generate_weekly_report() -> anyhow::Result<()> {
     Ok(())
 }
 
-fn load_config() -> anyhow::Result<Config> {
-    if Path::new(PROMPTS_FILE).exists() {
-        let prompts = fs::read_to_string(PROMPTS_FILE)?
-            .lines()
-            .map(|s| s.trim().to_string())
-            .filter(|s| !s.is_empty())
-            .collect();
-        
-        return Ok(Config {
-            reality_check_prompts: prompts,
-        });
+fn print_dry_spell_nudge(journal: &Journal) -> anyhow::Result<()> {
+    let threshold = journal.config()?.dry_spell_days as i64;
+    let today = Utc::now().date_naive();
+    
+    if let Some(days) = dry_spell_days(&journal.dreams()?, &journal.daily_logs()?, today)
+        && days >= threshold {
+        decor!("\n🌙 It's been {} days since you last recalled a dream. Dry spells are normal — recall comes back.", days);
+        decor!("  - Keep up your reality checks during the day");
+        decor!("  - Keep your journal by the bed and write something, even a feeling, right after waking");
+    }
+    
+    Ok(())
+}
+
+fn show_status(journal: &Journal) -> anyhow::Result<()> {
+    let today = Utc::now().date_naive();
+    
+    match dry_spell_days(&journal.dreams()?, &journal.daily_logs()?, today) {
+        None => println!("No dreams recorded yet."),
+        Some(0) => println!("Last dream recalled today."),
+        Some(days) => println!("Last dream recalled {} days ago.", days),
     }
+    print_dry_spell_nudge(journal)?;
+    print_daily_reminder(journal)?;
     
-    if Path::new(CONFIG_FILE).exists() {
-        let data = fs::read_to_string(CONFIG_FILE)?;
-        return Ok(serde_json::from_str(&data)?);
+    Ok(())
+}
+
+fn show_config(journal: &Journal) -> anyhow::Result<()> {
+    println!("{}", serde_json::to_string_pretty(&journal.config()?)?);
+    if journal.has_prompts_file() {
+        decor!("\nreality_check_prompts includes the lines of prompts.txt, added after those in config.json.");
+    }
+    Ok(())
+}
+
+fn set_config(journal: &Journal, key: &str, value: &str) -> anyhow::Result<()> {
+    if key == "dream_storage" {
+        anyhow::bail!(JournalError::InvalidInput(format!(
+            "Use `dream migrate-storage --to {}` so your dreams move with the setting", value)));
+    }
+    // Values are JSON when they parse as JSON (numbers, true, null, lists), plain strings otherwise.
+    let parsed = serde_json::from_str(value).unwrap_or_else(|_| serde_json::Value::String(value.to_string()));
+    journal.set_config_value(key, parsed.clone())?;
+    println!("{} = {}", key, parsed);
+    Ok(())
+}
+
+fn verify(journal: &Journal) -> anyhow::Result<()> {
+    let reports = journal.verify();
+    let mut problems = 0;
+    
+    for report in &reports {
+        if report.passed() {
+            println!("{} {}", paint("PASS", Style::Section), report.file);
+        } else {
+            println!("{} {}", paint("FAIL", Style::Bad), report.file);
+            for problem in &report.problems {
+                println!("  - {}", problem);
+            }
+            problems += report.problems.len();
+        }
+    }
+    
+    if problems > 0 {
+        anyhow::bail!(JournalError::Corrupt(format!("Verification found {} problem(s)", problems)));
+    }
+    println!("\nAll data files look good.");
+    Ok(())
+}
+
+fn print_daily_reminder(journal: &Journal) -> anyhow::Result<()> {
+    if let Some(hour) = journal.config()?.daily_reminder_hour
+        && daily_log_missing(&journal.daily_logs()?, Local::now(), hour) {
+        decor!("\n📝 You haven't logged today yet — run `lucid-dreamer daily`.");
     }
     
-    Ok(Config::default())
+    Ok(())
 }
 
-fn reality_check() -> anyhow::Result<()> {
-    let config = load_config()?;
+fn reality_check(journal: &Journal, category: Option<&str>, log: bool, anchor: Option<&str>, count: usize, rng: &mut StdRng) -> anyhow::Result<()> {
+    let config = journal.config()?;
+    let anchor = anchor.map(|name| {
+        config.reality_check_anchors.iter()
+            .find(|a| a.eq_ignore_ascii_case(name))
+            .ok_or_else(|| JournalError::InvalidInput(if config.reality_check_anchors.is_empty() {
+                format!("No anchors configured; add some with `config set reality_check_anchors '[\"{}\", \"phone\"]'`", name)
+            } else {
+                format!("Unknown anchor '{}'. Configured anchors: {}", name, config.reality_check_anchors.join(", "))
+            }))
+    }).transpose()?;
+    let log = log || anchor.is_some();
     if config.reality_check_prompts.is_empty() {
         return Err(anyhow::anyhow!("No reality check prompts found"));
     }
     
-    let prompt = config.reality_check_prompts
-        .choose(&mut rand::thread_rng())
-        .unwrap();
+    let pool: Vec<_> = config.reality_check_prompts.iter()
+        .filter(|p| category.is_none_or(|c| p.category().eq_ignore_ascii_case(c)))
+        .collect();
+    if pool.is_empty() {
+        anyhow::bail!(JournalError::NotFound(format!("No reality check prompts in category '{}'", category.unwrap_or_default())));
+    }
+    
+    // Draw without replacement, starting a fresh round once every prompt has been shown.
+    let mut prompts: Vec<&RealityCheckPrompt> = Vec::with_capacity(count);
+    while prompts.len() < count.max(1) {
+        let round = (count.max(1) - prompts.len()).min(pool.len());
+        prompts.extend(pool
+            .choose_multiple_weighted(rng, round, |p| p.weight().max(0.0))
+            .map_err(|_| anyhow::anyhow!("Reality check prompt weights must include a positive value"))?);
+    }
+    
+    for (i, check) in prompts.iter().enumerate() {
+        if i > 0 {
+            thread::sleep(Duration::from_secs(REALITY_CHECK_PAUSE_SECS));
+        }
+        if prompts.len() > 1 {
+            println!("\nREALITY CHECK {}/{}: {}\n", i + 1, prompts.len(), check.text());
+            play_short_beep();
+        } else {
+            println!("\nREALITY CHECK: {}\n", check.text());
+        }
+        if config.desktop_notifications {
+            notify("Reality check", check.text());
+        }
+        
+        if log {
+            let answer = prompt_optional("AWARE", "Did it trigger any lucidity or heightened awareness? (y/N): ")?;
+            journal.record_reality_check(check.text(), answer.trim().eq_ignore_ascii_case("y"), anchor.map(String::as_str))?;
+        }
+    }
+    Ok(())
+}
+
+fn reality_check_report(journal: &Journal) -> anyhow::Result<()> {
+    let week_start = journal.config()?.week_start.weekday();
+    let weeks = reality_check_weeks(&journal.daily_logs()?, &journal.dreams()?, &journal.reality_check_log()?, week_start);
+    
+    decor!("\n{}", paint("REALITY CHECKS VS LUCIDITY", Style::Heading));
+    decor!("===============================================");
+    if weeks.is_empty() {
+        println!("\nNo reality checks or dreams recorded yet.");
+        return Ok(());
+    }
+    
+    println!("\n{:<12} {:>7} {:>7} {:>7} {:>8}", "Week of", "Checks", "Aware", "Dreams", "Lucid");
+    for week in &weeks {
+        let lucid_rate = if week.dreams == 0 {
+            "—".to_string()
+        } else {
+            format!("{:.0}%", week.lucid as f32 / week.dreams as f32 * 100.0)
+        };
+        println!("{:<12} {:>7} {:>7} {:>7} {:>8}",
+            week.start.to_string(), week.checks, week.aware, week.dreams, lucid_rate);
+    }
+    
+    Ok(())
+}
+
+fn anchor_report(journal: &Journal) -> anyhow::Result<()> {
+    let anchors = journal.config()?.reality_check_anchors;
+    let usage = anchor_usage(&anchors, &journal.reality_check_log()?);
+    
+    decor!("\n{}", paint("REALITY CHECK ANCHORS", Style::Heading));
+    decor!("===============================================");
+    if usage.is_empty() {
+        println!("\nNo anchors yet. Configure some with `config set reality_check_anchors '[\"doorway\", \"phone\"]'`,");
+        println!("then log checks with `reality-check --anchor doorway`.");
+        return Ok(());
+    }
+    
+    let today = Local::now().date_naive();
+    println!("\n{:<20} {:>7} {:>7}  {:<12}", "Anchor", "Checks", "Aware", "Last used");
+    for anchor in &usage {
+        let last_used = anchor.last_used.map_or_else(|| "never".to_string(), |d| d.to_string());
+        let line = format!("{:<20} {:>7} {:>7}  {:<12}", anchor.anchor, anchor.checks, anchor.aware, last_used);
+        if anchor.neglected(today, ANCHOR_NEGLECT_DAYS) {
+            println!("{} {}", line, paint("neglected", Style::Bad));
+        } else {
+            println!("{}", line.trim_end());
+        }
+    }
+    
+    let neglected = usage.iter().filter(|a| a.neglected(today, ANCHOR_NEGLECT_DAYS)).count();
+    if neglected > 0 {
+        decor!("\n{} of {} anchors unused in the last {} days — try tying a check to them today.",
+            neglected, usage.len(), ANCHOR_NEGLECT_DAYS);
+    }
+    Ok(())
+}
+
+fn control_report(journal: &Journal, sessions: usize) -> anyhow::Result<()> {
+    let history = journal.technique_history()?;
+    let mut techniques: Vec<&str> = history.iter().map(|p| p.technique.as_str()).collect();
+    techniques.sort();
+    techniques.dedup();
+    let mut levels: Vec<(&str, Vec<u8>)> = techniques.into_iter()
+        .map(|technique| (technique, control_levels(&history, Some(technique))))
+        .filter(|(_, levels)| !levels.is_empty())
+        .collect();
+    
+    decor!("\n{}", paint("CONTROL LEVEL TRENDS", Style::Heading));
+    decor!("===============================================");
+    if levels.is_empty() {
+        println!("\nNo full-lucid sessions recorded yet.");
+        return Ok(());
+    }
+    if levels.len() > 1 {
+        levels.insert(0, ("All techniques", control_levels(&history, None)));
+    }
+    
+    println!("\n{:<16} {:>8} {:>8}  {:<15} Sessions", "Technique", "Lucid", "Avg", "Trend");
+    for (technique, levels) in &levels {
+        let average = levels.iter().map(|&l| f32::from(l)).sum::<f32>() / levels.len() as f32;
+        let trend = match control_trend(levels, sessions) {
+            Some(change) if change >= 0.5 => paint(format!("{:<15}", format!("improving {:+.1}", change)), Style::Accent),
+            Some(change) if change <= -0.5 => paint(format!("{:<15}", format!("declining {:+.1}", change)), Style::Bad),
+            Some(change) => format!("{:<15}", format!("steady {:+.1}", change)),
+            None => format!("{:<15}", "—"),
+        };
+        let values: Vec<Option<f32>> = levels.iter().map(|&l| Some(f32::from(l))).collect();
+        println!("{:<16} {:>8} {:>8.1}  {} {}",
+            technique, levels.len(), average, trend, sparkline(&values, 1.0, 5.0));
+    }
+    decor!("\nTrend compares the older and newer half of the last {} sessions (control 1-5).", sessions);
     
-    println!("\nREALITY CHECK: {}\n", prompt);
     Ok(())
 }
 
-fn print_daily_summary(log: &DailyLog) {
+fn watch_reality_checks(journal: &Journal, category: Option<&str>, minutes: u64, log: bool, count: usize, rng: &mut StdRng) -> anyhow::Result<()> {
+    let interval = Duration::from_secs(minutes.max(1) * 60);
+    println!("Reality check every {} minutes. Press 'q' to stop.", minutes.max(1));
+    
+    loop {
+        reality_check(journal, category, log, None, count, rng)?;
+        let next = Instant::now() + interval;
+        while Instant::now() < next {
+            if quit_pressed(Duration::from_millis(100))? {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// Shows a desktop notification; a missing notification service just means no popup.
+fn notify(summary: &str, body: &str) {
+    let _ = notify_rust::Notification::new()
+        .appname("Lucid Dreamer")
+        .summary(summary)
+        .body(body)
+        .show();
+}
+
+fn print_daily_summary(log: &DailyLog, time_format: TimeFormat, quality_scale: u8) {
     println!("\n--- DAILY SUMMARY FOR {} ---", log.date);
     
-    if let Some(sleep) = &log.sleep {
-        println!("Sleep: {} to {} (Quality: {}/5)", 
-            sleep.bedtime, sleep.wake_time, sleep.quality);
+    for (i, sleep) in log.sleep.iter().enumerate() {
+        println!("{}: {} to {} (Quality: {}/{})", if i == 0 { "Sleep" } else { "Nap" },
+            time_format.format_str(&sleep.bedtime), time_format.format_str(&sleep.wake_time), sleep.quality, quality_scale);
     }
     
     if let Some(dream) = &log.dream {
@@ -1189,81 +3454,24 @@ fn print_daily_summary(log: &DailyLog) {
     }
     
     if let Some(feeling) = &log.wake_feeling {
-        println!("Wake feeling: {}", feeling);
+        println!("Wake feeling: {}", feeling.replace('\n', "\n              "));
     }
     
     println!("Reality checks: {}", log.reality_checks);
+
+    if !log.tags.is_empty() {
+        println!("Tags: {}", log.tags.join(", "));
+    }
     
     if let Some(alarm_id) = log.wbtb_alarm_used {
-        println!("WBTB Alarm used: #{}", alarm_id);
+        match log.wbtb_awake_minutes {
+            Some(minutes) => println!("WBTB Alarm used: #{} (awake {} min)", alarm_id, minutes),
+            None => println!("WBTB Alarm used: #{}", alarm_id),
+        }
     }
     
     if !log.notes.is_empty() {
-        println!("Notes: {}", log.notes);
-    }
-}
-
-fn load_techniques() -> anyhow::Result<HashMap<String, TechniqueData>> {
-    if Path::new(TECHNIQUES_FILE).exists() {
-        let data = fs::read_to_string(TECHNIQUES_FILE)?;
-        return Ok(serde_json::from_str(&data)?);
+        println!("Notes: {}", log.notes.replace('\n', "\n       "));
     }
-    
-    let mut techniques = HashMap::new();
-    
-    techniques.insert("MILD".to_string(), TechniqueData {
-        name: "Mnemonic Induction of Lucid Dreams (MILD)".to_string(),
-        description: "A technique that uses prospective memory to increase lucid dream frequency".to_string(),
-        steps: vec![
-            "Set intention to remember you're dreaming".to_string(),
-            "Visualize yourself becoming lucid in a recent dream".to_string(),
-            "Repeat a mantra like 'Next time I'm dreaming, I'll remember I'm dreaming'".to_string(),
-            "Fall asleep while maintaining this intention".to_string(),
-        ],
-        last_practiced: None,
-    });
-    
-    techniques.insert("WBTB".to_string(), TechniqueData {
-        name: "Wake Back To Bed (WBTB)".to_string(),
-        description: "Wake up after 4-6 hours of sleep, stay awake briefly, then return to sleep".to_string(),
-        steps: vec![
-            "Set alarm for 4-6 hours after bedtime".to_string(),
-            "When alarm goes off, stay awake for 20-60 minutes".to_string(),
-            "Engage in lucid dream preparation activities".to_string(),
-            "Return to sleep while maintaining awareness".to_string(),
-        ],
-        last_practiced: None,
-    });
-    
-    techniques.insert("FILD".to_string(), TechniqueData {
-        name: "Finger Induced Lucid Dream (FILD)".to_string(),
-        description: "A subtle finger movement technique to enter directly into a lucid dream".to_string(),
-        steps: vec![
-            "Wake up after 4-6 hours of sleep".to_string(),
-            "Lie completely still".to_string(),
-            "Gently move index and middle fingers as if playing piano".to_string(),
-            "After 10-20 seconds, perform a reality check".to_string(),
-        ],
-        last_practiced: None,
-    });
-    
-    techniques.insert("RC".to_string(), TechniqueData {
-        name: "Reality Checks".to_string(),
-        description: "Habitual checks throughout the day to test if you're dreaming".to_string(),
-        steps: vec![
-            "Perform 10+ reality checks daily".to_string(),
-            "Question your reality: 'Am I dreaming?'".to_string(),
-            "Examine your environment for dream signs".to_string(),
-            "Try to push finger through palm or read text twice".to_string(),
-        ],
-        last_practiced: None,
-    });
-    
-    Ok(techniques)
 }
 
-fn save_techniques(techniques: &HashMap<String, TechniqueData>) -> anyhow::Result<()> {
-    let data = serde_json::to_string_pretty(techniques)?;
-    fs::write(TECHNIQUES_FILE, data)?;
-    Ok(())
-}